@@ -0,0 +1,59 @@
+/*  Parser for /proc/softnet_stats file
+ *  Copyright (C) 2016  Herman J. Radtke III <herman@hermanradtke.com>
+ *
+ *  This program is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Highlighting rows of the table with ANSI colors.
+
+pub const RED: &str = "\x1b[31m";
+pub const YELLOW: &str = "\x1b[33m";
+pub const RESET: &str = "\x1b[0m";
+
+/// Whether the output should be colored, given the value of `--color`.
+///
+/// `auto` colors only when stdout is a terminal, so piped output stays plain.
+pub fn enabled(when: &str) -> Result<bool, String> {
+    match when {
+        "auto" => Ok(stdout_is_terminal()),
+        "always" => Ok(true),
+        "never" => Ok(false),
+        _ => Err(format!("`{}` is not one of auto, always or never", when)),
+    }
+}
+
+#[cfg(unix)]
+fn stdout_is_terminal() -> bool {
+    const STDOUT_FILENO: i32 = 1;
+
+    extern "C" {
+        fn isatty(fd: i32) -> i32;
+    }
+
+    // Safety: isatty only inspects the file descriptor.
+    unsafe { isatty(STDOUT_FILENO) == 1 }
+}
+
+#[cfg(not(unix))]
+fn stdout_is_terminal() -> bool {
+    false
+}
+
+#[test]
+fn test_enabled() {
+    assert_eq!(Ok(true), enabled("always"));
+    assert_eq!(Ok(false), enabled("never"));
+    assert!(enabled("auto").is_ok());
+    assert!(enabled("yes").is_err());
+}
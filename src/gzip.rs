@@ -0,0 +1,504 @@
+/*  Parser for /proc/softnet_stats file
+ *  Copyright (C) 2016  Herman J. Radtke III <herman@hermanradtke.com>
+ *
+ *  This program is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Decompressing gzip archives of softnet_stat captures.
+//!
+//! Captures are small, so this is a straightforward inflater in the style of zlib's puff.c rather
+//! than a fast one. It follows RFC 1951 for the deflate data and RFC 1952 for the gzip framing.
+
+use std::io;
+
+/// The first two bytes of every gzip member.
+pub const MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+const FHCRC: u8 = 0x02;
+const FEXTRA: u8 = 0x04;
+const FNAME: u8 = 0x08;
+const FCOMMENT: u8 = 0x10;
+
+/// The most that an archive is allowed to decompress to. A softnet_stat file is about 120 bytes per
+/// CPU, so this is far more than any real capture, but stops a small crafted archive from
+/// inflating into gigabytes.
+pub const MAX_OUTPUT: usize = 8 * 1024 * 1024;
+
+/// The longest Huffman code deflate allows.
+const MAX_BITS: usize = 15;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+/// The order the code length code lengths of a dynamic block are written in.
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+/// Check that `len` more bytes can be written to `out` without going over `MAX_OUTPUT`.
+fn room(out: &[u8], len: usize) -> io::Result<()> {
+    if out.len() + len > MAX_OUTPUT {
+        return Err(invalid("decompresses to more than 8 MiB"));
+    }
+
+    Ok(())
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("invalid gzip data: {}", message),
+    )
+}
+
+/// Whether `input` starts like a gzip archive.
+pub fn is_gzip(input: &[u8]) -> bool {
+    input.starts_with(&MAGIC)
+}
+
+/// Decompress every member of the gzip archive `input`, checking the CRC and length of each.
+pub fn decompress(input: &[u8]) -> io::Result<Vec<u8>> {
+    let mut out = vec![];
+    let mut rest = input;
+
+    // `gzip a b > c` writes one member per file, which decompress to their concatenation.
+    while !rest.is_empty() {
+        let start = out.len();
+        let body = header(rest)?;
+        let used = inflate(body, &mut out)?;
+
+        let trailer = body
+            .get(used..used + 8)
+            .ok_or_else(|| invalid("truncated trailer"))?;
+        let crc = u32::from_le_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+        let size = u32::from_le_bytes([trailer[4], trailer[5], trailer[6], trailer[7]]);
+        if crc != crc32(&out[start..]) {
+            return Err(invalid("crc mismatch"));
+        }
+        if size != (out.len() - start) as u32 {
+            return Err(invalid("length mismatch"));
+        }
+
+        rest = &body[used + 8..];
+    }
+
+    Ok(out)
+}
+
+/// Skip the header of the gzip member at the start of `input`, returning the deflate data.
+fn header(input: &[u8]) -> io::Result<&[u8]> {
+    if input.len() < 10 || !is_gzip(input) {
+        return Err(invalid("bad header"));
+    }
+    if input[2] != 8 {
+        return Err(invalid("unknown compression method"));
+    }
+
+    let flags = input[3];
+    let mut rest = &input[10..];
+    if flags & FEXTRA != 0 {
+        let len = rest.get(..2).ok_or_else(|| invalid("truncated header"))?;
+        let len = usize::from(u16::from_le_bytes([len[0], len[1]]));
+        rest = skip(rest, 2 + len)?;
+    }
+    if flags & FNAME != 0 {
+        rest = skip_string(rest)?;
+    }
+    if flags & FCOMMENT != 0 {
+        rest = skip_string(rest)?;
+    }
+    if flags & FHCRC != 0 {
+        rest = skip(rest, 2)?;
+    }
+
+    Ok(rest)
+}
+
+fn skip(input: &[u8], n: usize) -> io::Result<&[u8]> {
+    input.get(n..).ok_or_else(|| invalid("truncated header"))
+}
+
+/// Skip past the zero terminated string at the start of `input`.
+fn skip_string(input: &[u8]) -> io::Result<&[u8]> {
+    match input.iter().position(|&b| b == 0) {
+        Some(end) => Ok(&input[end + 1..]),
+        None => Err(invalid("truncated header")),
+    }
+}
+
+/// Reads the bits of deflate data, least significant bit of each byte first.
+struct Bits<'a> {
+    input: &'a [u8],
+    /// The position of the next bit.
+    pos: usize,
+}
+
+impl Bits<'_> {
+    fn bits(&mut self, count: u32) -> io::Result<u32> {
+        let mut value = 0;
+
+        for i in 0..count {
+            let byte = self
+                .input
+                .get(self.pos / 8)
+                .ok_or_else(|| invalid("unexpected end of data"))?;
+            value |= u32::from((byte >> (self.pos % 8)) & 1) << i;
+            self.pos += 1;
+        }
+
+        Ok(value)
+    }
+
+    /// Skip to the start of the next byte, and return its position.
+    fn align(&mut self) -> usize {
+        self.pos = (self.pos + 7) / 8 * 8;
+        self.pos / 8
+    }
+}
+
+/// A canonical Huffman code, stored as the number of codes of each length and the symbols in
+/// code order.
+struct Huffman {
+    counts: [u16; MAX_BITS + 1],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    /// Build the code in which symbol `i` has a code `lengths[i]` bits long, or none if it is 0.
+    fn new(lengths: &[u8]) -> Huffman {
+        let mut counts = [0u16; MAX_BITS + 1];
+        for &length in lengths {
+            counts[usize::from(length)] += 1;
+        }
+        counts[0] = 0;
+
+        let mut offsets = [0u16; MAX_BITS + 1];
+        for length in 1..MAX_BITS {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                let offset = &mut offsets[usize::from(length)];
+                symbols[usize::from(*offset)] = symbol as u16;
+                *offset += 1;
+            }
+        }
+
+        Huffman { counts, symbols }
+    }
+
+    fn decode(&self, bits: &mut Bits) -> io::Result<u16> {
+        // The first code of each length, and the index of its symbol.
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+
+        for &count in &self.counts[1..] {
+            code |= bits.bits(1)? as i32;
+            let count = i32::from(count);
+            if code - first < count {
+                return self
+                    .symbols
+                    .get((index + code - first) as usize)
+                    .copied()
+                    .ok_or_else(|| invalid("bad code"));
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+
+        Err(invalid("bad code"))
+    }
+}
+
+/// Inflate the deflate data at the start of `input` onto `out`, returning how many bytes of
+/// `input` it used.
+fn inflate(input: &[u8], out: &mut Vec<u8>) -> io::Result<usize> {
+    let mut bits = Bits { input, pos: 0 };
+    // Each member is compressed on its own, so back references cannot reach into earlier ones.
+    let start = out.len();
+
+    loop {
+        let last = bits.bits(1)? == 1;
+
+        match bits.bits(2)? {
+            0 => stored(&mut bits, out)?,
+            1 => {
+                let mut lengths = [0u8; 288];
+                lengths[..144].fill(8);
+                lengths[144..256].fill(9);
+                lengths[256..280].fill(7);
+                lengths[280..].fill(8);
+                let lengths = Huffman::new(&lengths);
+                let distances = Huffman::new(&[5; 30]);
+                codes(&mut bits, out, start, &lengths, &distances)?;
+            }
+            2 => {
+                let (lengths, distances) = dynamic(&mut bits)?;
+                codes(&mut bits, out, start, &lengths, &distances)?;
+            }
+            _ => return Err(invalid("bad block type")),
+        }
+
+        if last {
+            return Ok(bits.align());
+        }
+    }
+}
+
+fn stored(bits: &mut Bits, out: &mut Vec<u8>) -> io::Result<()> {
+    let pos = bits.align();
+    let header = bits
+        .input
+        .get(pos..pos + 4)
+        .ok_or_else(|| invalid("truncated stored block"))?;
+    let len = u16::from_le_bytes([header[0], header[1]]);
+    if !len != u16::from_le_bytes([header[2], header[3]]) {
+        return Err(invalid("bad stored block length"));
+    }
+
+    let data = bits
+        .input
+        .get(pos + 4..pos + 4 + usize::from(len))
+        .ok_or_else(|| invalid("truncated stored block"))?;
+    room(out, data.len())?;
+    out.extend_from_slice(data);
+    bits.pos = (pos + 4 + usize::from(len)) * 8;
+
+    Ok(())
+}
+
+/// Read the literal/length and distance codes at the start of a dynamic block.
+fn dynamic(bits: &mut Bits) -> io::Result<(Huffman, Huffman)> {
+    let literals = bits.bits(5)? as usize + 257;
+    let distances = bits.bits(5)? as usize + 1;
+    let code_lengths = bits.bits(4)? as usize + 4;
+    if literals > 286 || distances > 30 {
+        return Err(invalid("too many codes"));
+    }
+
+    let mut lengths = [0u8; 19];
+    for &symbol in &CODE_LENGTH_ORDER[..code_lengths] {
+        lengths[symbol] = bits.bits(3)? as u8;
+    }
+    let code = Huffman::new(&lengths);
+
+    let mut lengths = vec![];
+    while lengths.len() < literals + distances {
+        let (length, repeat) = match code.decode(bits)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 => {
+                let previous = *lengths
+                    .last()
+                    .ok_or_else(|| invalid("repeat with no first length"))?;
+                (previous, 3 + bits.bits(2)?)
+            }
+            17 => (0, 3 + bits.bits(3)?),
+            _ => (0, 11 + bits.bits(7)?),
+        };
+        lengths.extend((0..repeat).map(|_| length));
+    }
+    if lengths.len() > literals + distances {
+        return Err(invalid("too many lengths"));
+    }
+    if lengths[256] == 0 {
+        return Err(invalid("no end of block code"));
+    }
+
+    Ok((
+        Huffman::new(&lengths[..literals]),
+        Huffman::new(&lengths[literals..]),
+    ))
+}
+
+/// Decode the literals and back references of a compressed block until its end of block code.
+fn codes(
+    bits: &mut Bits,
+    out: &mut Vec<u8>,
+    start: usize,
+    lengths: &Huffman,
+    distances: &Huffman,
+) -> io::Result<()> {
+    loop {
+        let symbol = usize::from(lengths.decode(bits)?);
+
+        if symbol < 256 {
+            room(out, 1)?;
+            out.push(symbol as u8);
+        } else if symbol == 256 {
+            return Ok(());
+        } else {
+            let symbol = symbol - 257;
+            if symbol >= LENGTH_BASE.len() {
+                return Err(invalid("bad length code"));
+            }
+            let length = usize::from(LENGTH_BASE[symbol])
+                + bits.bits(u32::from(LENGTH_EXTRA[symbol]))? as usize;
+
+            let symbol = usize::from(distances.decode(bits)?);
+            if symbol >= DISTANCE_BASE.len() {
+                return Err(invalid("bad distance code"));
+            }
+            let distance = usize::from(DISTANCE_BASE[symbol])
+                + bits.bits(u32::from(DISTANCE_EXTRA[symbol]))? as usize;
+            if distance > out.len() - start {
+                return Err(invalid("distance too far back"));
+            }
+
+            room(out, length)?;
+            // The source and destination can overlap, repeating the last `distance` bytes.
+            let from = out.len() - distance;
+            for i in 0..length {
+                out.push(out[from + i]);
+            }
+        }
+    }
+}
+
+/// The CRC-32 of `data`, as used by gzip.
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+
+    !crc
+}
+
+#[test]
+fn test_crc32() {
+    assert_eq!(0, crc32(b""));
+    assert_eq!(0xcbf4_3926, crc32(b"123456789"));
+}
+
+#[cfg(test)]
+fn fixture() -> Vec<u8> {
+    // A 32 CPU capture written by `gzip -9`, which is large enough to get a dynamic Huffman block.
+    let pwd = env!("CARGO_MANIFEST_DIR");
+    std::fs::read(format!(
+        "{}/tests/proc-net-softnet_stat-5_10_47-32_cpu-gzip",
+        pwd
+    ))
+    .unwrap()
+}
+
+#[test]
+fn test_decompress() {
+    let compressed = fixture();
+    assert!(is_gzip(&compressed));
+
+    let raw = decompress(&compressed).unwrap();
+    let text = String::from_utf8(raw.clone()).unwrap();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(32, lines.len());
+    assert_eq!(
+        "50bb3e22 00000000 00000ed9 00000000 00000000 00000000 00000000 00000000 00000000 000ee661 00000000 00000080 00000000",
+        lines[0]
+    );
+    assert!(lines[31].ends_with(" 0000001f"));
+
+    // Two members decompress to the concatenation of their contents.
+    let twice = [&compressed[..], &compressed[..]].concat();
+    assert_eq!([&raw[..], &raw[..]].concat(), decompress(&twice).unwrap());
+}
+
+#[test]
+fn test_decompress_fixed_huffman() {
+    // `hello hello hello\n` compressed with a single fixed Huffman block.
+    let compressed = [
+        0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03, 0xcb, 0x48, 0xcd, 0xc9, 0xc9,
+        0x57, 0xc8, 0x40, 0x90, 0x5c, 0x00, 0x3b, 0x7c, 0x8a, 0xdf, 0x12, 0x00, 0x00, 0x00,
+    ];
+
+    assert_eq!(
+        b"hello hello hello\n".to_vec(),
+        decompress(&compressed).unwrap()
+    );
+}
+
+#[test]
+fn test_decompress_corrupt() {
+    let compressed = fixture();
+
+    let mut corrupt = compressed.clone();
+    let crc = corrupt.len() - 8;
+    corrupt[crc] ^= 1;
+    assert!(decompress(&corrupt).is_err());
+
+    assert!(decompress(&compressed[..compressed.len() - 4]).is_err());
+    assert!(decompress(&compressed[..20]).is_err());
+    assert!(decompress(&MAGIC).is_err());
+}
+
+#[test]
+fn test_decompress_too_large() {
+    // A fixed Huffman block of one literal followed by back references that each repeat it 258
+    // times, which is how zeros compress. Codes are written most significant bit first.
+    let mut deflate = vec![];
+    let (mut byte, mut used) = (0u8, 0);
+    let mut put = |code: u32, len: u32| {
+        for i in (0..len).rev() {
+            byte |= (((code >> i) & 1) as u8) << used;
+            used += 1;
+            if used == 8 {
+                deflate.push(byte);
+                byte = 0;
+                used = 0;
+            }
+        }
+    };
+    // BFINAL, then BTYPE 01 for fixed codes, which is read least significant bit first.
+    put(1, 1);
+    put(1, 1);
+    put(0, 1);
+    // The literal 0, then length 258 at distance 1, until past the limit.
+    put(0b0011_0000, 8);
+    for _ in 0..MAX_OUTPUT / 258 + 1 {
+        put(0b1100_0101, 8);
+        put(0, 5);
+    }
+    put(0, 7);
+    put(0, 7);
+
+    let mut compressed = vec![0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03];
+    compressed.extend_from_slice(&deflate);
+    compressed.extend_from_slice(&[0; 8]);
+    assert!(compressed.len() < 64 * 1024);
+
+    let e = decompress(&compressed).unwrap_err();
+    assert_eq!(io::ErrorKind::InvalidData, e.kind());
+    assert!(e.to_string().contains("more than 8 MiB"), "{}", e);
+}
@@ -0,0 +1,108 @@
+/*  Parser for /proc/softnet_stats file
+ *  Copyright (C) 2016  Herman J. Radtke III <herman@hermanradtke.com>
+ *
+ *  This program is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Stopping a long running loop cleanly when the user presses Ctrl-C.
+
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Replace the default SIGINT action, which kills the process, with one that only records the
+/// interrupt so the caller can finish what it is writing and stop.
+///
+/// A read that is blocked when SIGINT arrives fails with `Interrupted` instead of being restarted,
+/// so that `read_until` can stop waiting for input that may never come.
+#[cfg(unix)]
+pub fn install() -> io::Result<()> {
+    const SIGINT: i32 = 2;
+    const SIG_ERR: usize = !0;
+
+    extern "C" {
+        fn signal(signum: i32, handler: extern "C" fn(i32)) -> usize;
+        fn siginterrupt(signum: i32, flag: i32) -> i32;
+    }
+
+    extern "C" fn handle(_signum: i32) {
+        INTERRUPTED.store(true, Ordering::SeqCst);
+    }
+
+    // Safety: the handler only stores to an atomic, which is async-signal-safe.
+    if unsafe { signal(SIGINT, handle) } == SIG_ERR {
+        return Err(io::Error::last_os_error());
+    }
+    // Safety: this only clears SA_RESTART from the action installed above.
+    if unsafe { siginterrupt(SIGINT, 1) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn install() -> io::Result<()> {
+    Ok(())
+}
+
+/// Whether SIGINT has been received since `install` was called.
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// Read into `buf` up to and including the next `byte`, like `BufRead::read_until`.
+///
+/// `read_until` retries reads interrupted by any signal, which would leave Ctrl-C waiting for the
+/// next line of input. This retries them only until SIGINT has been received, then fails with
+/// `Interrupted`.
+pub fn read_until<R>(input: &mut R, byte: u8, buf: &mut Vec<u8>) -> io::Result<usize>
+where
+    R: io::BufRead,
+{
+    let mut read = 0;
+
+    loop {
+        let available = match input.fill_buf() {
+            Ok(available) => available,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted && !interrupted() => continue,
+            Err(e) => return Err(e),
+        };
+
+        let (done, used) = match available.iter().position(|b| *b == byte) {
+            Some(i) => (true, i + 1),
+            None => (available.is_empty(), available.len()),
+        };
+        buf.extend_from_slice(&available[..used]);
+        input.consume(used);
+        read += used;
+
+        if done {
+            return Ok(read);
+        }
+    }
+}
+
+#[test]
+fn test_read_until() {
+    let mut input = &b"one\ntwo"[..];
+    let mut buf = vec![];
+
+    assert_eq!(4, read_until(&mut input, b'\n', &mut buf).unwrap());
+    assert_eq!(b"one\n", &buf[..]);
+    assert_eq!(3, read_until(&mut input, b'\n', &mut buf).unwrap());
+    assert_eq!(b"one\ntwo", &buf[..]);
+    assert_eq!(0, read_until(&mut input, b'\n', &mut buf).unwrap());
+}
@@ -0,0 +1,277 @@
+/*  Parser for /proc/softnet_stats file
+ *  Copyright (C) 2016  Herman J. Radtke III <herman@hermanradtke.com>
+ *
+ *  This program is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Keys that control `--watch` from the terminal: `p` pauses and resumes, `q` quits and space
+//! refreshes now.
+
+use std::fs::File;
+use std::io::Read;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::interrupt;
+
+/// How often `wait` checks for a key or an interrupt.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A key that controls the watch.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Key {
+    Pause,
+    Quit,
+    Refresh,
+}
+
+impl Key {
+    fn from_byte(byte: u8) -> Option<Key> {
+        match byte {
+            b'p' | b'P' => Some(Key::Pause),
+            b'q' | b'Q' => Some(Key::Quit),
+            b' ' => Some(Key::Refresh),
+            _ => None,
+        }
+    }
+}
+
+/// What the watch should do once `wait` returns.
+#[derive(Debug, Eq, PartialEq)]
+pub enum Wake {
+    Refresh,
+    Quit,
+}
+
+/// The controlling terminal, switched to reading single keys without echoing them.
+///
+/// The settings it had before are put back when this is dropped, which also happens when the
+/// stack unwinds from a panic.
+pub struct Terminal {
+    tty: File,
+    saved: sys::Termios,
+}
+
+impl Terminal {
+    /// Start reading keys from the controlling terminal, or `None` if there is no terminal to read
+    /// them from, such as when running in the background or under a service manager.
+    pub fn open() -> Option<Terminal> {
+        let tty = File::open("/dev/tty").ok()?;
+        let saved = sys::enable_raw(&tty).ok()?;
+
+        Some(Terminal { tty, saved })
+    }
+
+    /// The next key that has been pressed, without waiting for one.
+    fn key(&mut self) -> Option<Key> {
+        let mut byte = [0];
+        loop {
+            match self.tty.read(&mut byte) {
+                Ok(1) => match Key::from_byte(byte[0]) {
+                    Some(key) => return Some(key),
+                    None => continue,
+                },
+                _ => return None,
+            }
+        }
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        let _ = sys::restore(&self.tty, &self.saved);
+    }
+}
+
+/// Wait out `duration`, or for as long as the watch is paused, while acting on keys.
+///
+/// `p` toggles `paused`, which carries over to the next wait. Space refreshes now, even while
+/// paused, and `q` or SIGINT quits.
+pub fn wait(duration: Duration, mut terminal: Option<&mut Terminal>, paused: &mut bool) -> Wake {
+    let deadline = Instant::now() + duration;
+
+    loop {
+        if interrupt::interrupted() {
+            return Wake::Quit;
+        }
+
+        match terminal.as_mut().and_then(|terminal| terminal.key()) {
+            Some(Key::Quit) => return Wake::Quit,
+            Some(Key::Refresh) => return Wake::Refresh,
+            Some(Key::Pause) => {
+                *paused = !*paused;
+                if *paused {
+                    eprintln!("paused; press p to resume");
+                } else {
+                    eprintln!("resumed");
+                }
+            }
+            None => {}
+        }
+
+        let now = Instant::now();
+        if now >= deadline && !*paused {
+            return Wake::Refresh;
+        }
+        if *paused {
+            thread::sleep(POLL_INTERVAL);
+        } else {
+            thread::sleep((deadline - now).min(POLL_INTERVAL));
+        }
+    }
+}
+
+/// termios for the Linux architectures that share the generic layout and control character
+/// indexes. Others differ in both, so keys are not read there.
+#[cfg(all(
+    target_os = "linux",
+    any(
+        target_arch = "x86",
+        target_arch = "x86_64",
+        target_arch = "arm",
+        target_arch = "aarch64",
+        target_arch = "riscv64"
+    )
+))]
+mod sys {
+    use std::fs::File;
+    use std::io;
+    use std::os::unix::io::AsRawFd;
+
+    const ICANON: u32 = 0o2;
+    const ECHO: u32 = 0o10;
+    const VTIME: usize = 5;
+    const VMIN: usize = 6;
+    const TCSANOW: i32 = 0;
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub struct Termios {
+        c_iflag: u32,
+        c_oflag: u32,
+        c_cflag: u32,
+        c_lflag: u32,
+        c_line: u8,
+        c_cc: [u8; 32],
+        c_ispeed: u32,
+        c_ospeed: u32,
+    }
+
+    extern "C" {
+        fn tcgetattr(fd: i32, termios: *mut Termios) -> i32;
+        fn tcsetattr(fd: i32, optional_actions: i32, termios: *const Termios) -> i32;
+        fn tcgetpgrp(fd: i32) -> i32;
+        fn getpgrp() -> i32;
+    }
+
+    /// Turn off line buffering and echo, and make reads return at once when no key is waiting.
+    /// Returns the settings to restore.
+    pub fn enable_raw(tty: &File) -> io::Result<Termios> {
+        let fd = tty.as_raw_fd();
+
+        // Changing the terminal from the background would stop the process with SIGTTOU.
+        // Safety: both only inspect the process group of the terminal and of this process.
+        if unsafe { tcgetpgrp(fd) != getpgrp() } {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "not in the foreground of the terminal",
+            ));
+        }
+
+        let mut saved = Termios {
+            c_iflag: 0,
+            c_oflag: 0,
+            c_cflag: 0,
+            c_lflag: 0,
+            c_line: 0,
+            c_cc: [0; 32],
+            c_ispeed: 0,
+            c_ospeed: 0,
+        };
+        // Safety: `saved` is a valid termios for tcgetattr to fill in.
+        if unsafe { tcgetattr(fd, &mut saved) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mut raw = saved;
+        raw.c_lflag &= !(ICANON | ECHO);
+        raw.c_cc[VMIN] = 0;
+        raw.c_cc[VTIME] = 0;
+        set(tty, &raw)?;
+
+        Ok(saved)
+    }
+
+    pub fn restore(tty: &File, saved: &Termios) -> io::Result<()> {
+        set(tty, saved)
+    }
+
+    fn set(tty: &File, termios: &Termios) -> io::Result<()> {
+        // Safety: `termios` is a valid termios that tcsetattr only reads.
+        if unsafe { tcsetattr(tty.as_raw_fd(), TCSANOW, termios) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(not(all(
+    target_os = "linux",
+    any(
+        target_arch = "x86",
+        target_arch = "x86_64",
+        target_arch = "arm",
+        target_arch = "aarch64",
+        target_arch = "riscv64"
+    )
+)))]
+mod sys {
+    use std::fs::File;
+    use std::io;
+
+    pub struct Termios;
+
+    pub fn enable_raw(_tty: &File) -> io::Result<Termios> {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            "watch keys are not supported on this platform",
+        ))
+    }
+
+    pub fn restore(_tty: &File, _saved: &Termios) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_key_from_byte() {
+    assert_eq!(Some(Key::Pause), Key::from_byte(b'p'));
+    assert_eq!(Some(Key::Quit), Key::from_byte(b'Q'));
+    assert_eq!(Some(Key::Refresh), Key::from_byte(b' '));
+    assert_eq!(None, Key::from_byte(b'x'));
+}
+
+#[test]
+fn test_wait() {
+    let start = Instant::now();
+    let mut paused = false;
+
+    assert_eq!(
+        Wake::Refresh,
+        wait(Duration::from_millis(10), None, &mut paused)
+    );
+    assert!(start.elapsed() >= Duration::from_millis(10));
+    assert!(!paused);
+}
@@ -0,0 +1,1353 @@
+/*  Parser for /proc/softnet_stats file
+ *  Copyright (C) 2016  Herman J. Radtke III <herman@hermanradtke.com>
+ *
+ *  This program is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Parse the `/proc/net/softnet_stat` file.
+//!
+//! The kernel writes one line of hex encoded counters for each online CPU. Newer kernels append
+//! more fields to each line, so the fields that were added later are optional.
+
+use std::ascii;
+use std::collections::BTreeMap;
+use std::error;
+use std::fmt;
+use std::io;
+
+use nom::branch::alt;
+use nom::bytes::complete::is_a;
+use nom::character::complete::line_ending;
+use nom::combinator::{eof, map, opt};
+use nom::error::{Error, ErrorKind};
+use nom::multi::many1;
+use nom::number::complete::hex_u32;
+use nom::sequence::{preceded, tuple};
+use nom::{AsBytes, Err, IResult, Needed};
+use serde_derive::{Deserialize, Serialize};
+
+#[cfg(feature = "gzip")]
+mod gzip;
+
+/// Network data processing statistics
+///
+/// The default value has every counter zero and none of the optional fields.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct SoftnetStat {
+    /// The number of network frames processed.
+    ///
+    /// This can be more than the total number of network frames received if
+    /// you are using ethernet bonding. There are cases where the ethernet
+    /// bonding driver will trigger network data to be re-processed, which
+    /// would increment the processed count more than once for the same packet.
+    pub processed: u32,
+
+    /// The number of network frames dropped because there was no room on the processing queue.
+    pub dropped: u32,
+
+    /// The number of times the `net_rx_action` loop terminated because the budget was consumed or
+    /// the time limit was reached, but more work could have been.
+    pub time_squeeze: u32,
+
+    /// The number of times the backlog queue was throttled.
+    ///
+    /// This slot is reserved and always zero on current kernels.
+    #[serde(default)]
+    pub throttled: u32,
+
+    /// The number of frames routed by the fast route feature.
+    ///
+    /// Fast routing was removed from the kernel; this slot is reserved and always zero.
+    #[serde(default)]
+    pub fastroute_hit: u32,
+
+    /// The number of frames the fast route feature handled successfully.
+    ///
+    /// Reserved and always zero, like `fastroute_hit`.
+    #[serde(default)]
+    pub fastroute_success: u32,
+
+    /// The number of frames the fast route feature deferred.
+    ///
+    /// Reserved and always zero, like `fastroute_hit`.
+    #[serde(default)]
+    pub fastroute_defer: u32,
+
+    /// The number of deferred frames the fast route feature sent out.
+    ///
+    /// Reserved and always zero, like `fastroute_hit`.
+    #[serde(default)]
+    pub fastroute_deferred_out: u32,
+
+    /// The number of times a collision occurred when trying to obtain a device lock
+    /// when transmitting packets.
+    ///
+    /// This was removed in kernel v4.7, but later kernels still write a zero in its place so the
+    /// fields that follow it do not move. It is always zero on those kernels.
+    pub cpu_collision: u32,
+
+    /// The number of times this CPU has been woken up to process packets via an Inter-processor Interrupt.
+    ///
+    /// Support was added in kernel v2.6.36
+    pub received_rps: Option<u32>,
+
+    /// The number of times the flow limit has been reached.
+    ///
+    /// Flow limiting is an optional Receive Packet Steering feature.
+    ///
+    /// Support was added in kernel v3.11
+    pub flow_limit_count: Option<u32>,
+
+    /// The network backlog length.
+    ///
+    /// Support was added in kernel v5.10
+    pub backlog_len: Option<u32>,
+
+    /// The cpu_id is the CPU id owning this softnet data.
+    ///
+    /// There is not a direct match between softnet_stat
+    /// lines and the related CPU. Offline CPUs are not dumped.
+    ///
+    /// Support was added in kernel v5.10
+    pub cpu_id: Option<u32>,
+}
+
+impl SoftnetStat {
+    /// A stat with the given counters, as older kernels report, and every other field zero or
+    /// absent.
+    ///
+    /// The other fields can be set by chaining the `with_` methods, which is shorter than a struct
+    /// literal and does not break when fields are added:
+    ///
+    /// ```
+    /// use softnet_stat::{parse_softnet_line, SoftnetStat};
+    ///
+    /// let line = b"00067f83 00000000 00000383 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000001\n";
+    /// let (_, parsed) = parse_softnet_line(line).unwrap();
+    ///
+    /// let stat = SoftnetStat::new(0x67f83, 0, 0x383)
+    ///     .with_received_rps(0)
+    ///     .with_flow_limit_count(0)
+    ///     .with_backlog_len(0)
+    ///     .with_cpu_id(1);
+    /// assert_eq!(parsed, stat);
+    /// ```
+    pub fn new(processed: u32, dropped: u32, time_squeeze: u32) -> SoftnetStat {
+        SoftnetStat {
+            processed,
+            dropped,
+            time_squeeze,
+            ..SoftnetStat::default()
+        }
+    }
+
+    /// Set the number of device lock collisions.
+    pub fn with_cpu_collision(self, cpu_collision: u32) -> SoftnetStat {
+        SoftnetStat {
+            cpu_collision,
+            ..self
+        }
+    }
+
+    /// Set the number of Inter-processor Interrupt wake ups, which kernels since v2.6.36 report.
+    pub fn with_received_rps(self, received_rps: u32) -> SoftnetStat {
+        SoftnetStat {
+            received_rps: Some(received_rps),
+            ..self
+        }
+    }
+
+    /// Set the number of times the flow limit was reached, which kernels since v3.11 report.
+    pub fn with_flow_limit_count(self, flow_limit_count: u32) -> SoftnetStat {
+        SoftnetStat {
+            flow_limit_count: Some(flow_limit_count),
+            ..self
+        }
+    }
+
+    /// Set the backlog length, which kernels since v5.10 report.
+    pub fn with_backlog_len(self, backlog_len: u32) -> SoftnetStat {
+        SoftnetStat {
+            backlog_len: Some(backlog_len),
+            ..self
+        }
+    }
+
+    /// Set the id of the CPU, which kernels since v5.10 report.
+    pub fn with_cpu_id(self, cpu_id: u32) -> SoftnetStat {
+        SoftnetStat {
+            cpu_id: Some(cpu_id),
+            ..self
+        }
+    }
+}
+
+/// The change in each counter of a [`SoftnetStat`] per second, between two samples of one CPU.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SoftnetRate {
+    /// Network frames processed per second.
+    pub processed: f64,
+
+    /// Network frames dropped per second.
+    pub dropped: f64,
+
+    /// Times the `net_rx_action` loop was squeezed per second.
+    pub time_squeeze: f64,
+
+    /// Device lock collisions per second.
+    pub cpu_collision: f64,
+
+    /// Inter-processor Interrupt wake ups per second, when both samples have them.
+    pub received_rps: Option<f64>,
+
+    /// Times the flow limit was reached per second, when both samples have them.
+    pub flow_limit_count: Option<f64>,
+
+    /// The backlog length of the later sample.
+    ///
+    /// This is the current length of a queue rather than a counter, so it has no rate.
+    pub backlog_len: Option<u32>,
+
+    /// The cpu_id of the later sample.
+    pub cpu_id: Option<u32>,
+}
+
+/// The per second rate of each counter between two samples taken `secs` seconds apart.
+///
+/// The counters are 32 bits wide and wrap around, so a counter that is lower in `cur` than in
+/// `prev` is assumed to have wrapped once rather than reset. Samples taken no time apart have a
+/// rate of zero rather than an infinite one.
+pub fn rate(prev: &SoftnetStat, cur: &SoftnetStat, secs: f64) -> SoftnetRate {
+    let per_second =
+        |prev: u32, cur: u32| ratio(f64::from(cur.wrapping_sub(prev)), secs).unwrap_or_default();
+    let optional = |prev: Option<u32>, cur: Option<u32>| Some(per_second(prev?, cur?));
+
+    SoftnetRate {
+        processed: per_second(prev.processed, cur.processed),
+        dropped: per_second(prev.dropped, cur.dropped),
+        time_squeeze: per_second(prev.time_squeeze, cur.time_squeeze),
+        cpu_collision: per_second(prev.cpu_collision, cur.cpu_collision),
+        received_rps: optional(prev.received_rps, cur.received_rps),
+        flow_limit_count: optional(prev.flow_limit_count, cur.flow_limit_count),
+        backlog_len: cur.backlog_len,
+        cpu_id: cur.cpu_id,
+    }
+}
+
+/// Read the entire contents of a softnet_stat file.
+///
+/// Archived captures are often gzipped, so with the `gzip` feature, input that starts with the gzip
+/// magic bytes is decompressed. A softnet_stat file starts with a hex digit, so it is never mistaken
+/// for one. Archives that decompress to more than 8 MiB are rejected with `InvalidData`, as is
+/// gzipped input when the feature is off.
+pub fn read_proc_file<R>(mut handle: R) -> io::Result<Vec<u8>>
+where
+    R: io::Read,
+{
+    let mut buf = vec![];
+    handle.read_to_end(&mut buf)?;
+
+    #[cfg(feature = "gzip")]
+    if gzip::is_gzip(&buf) {
+        return gzip::decompress(&buf);
+    }
+    #[cfg(not(feature = "gzip"))]
+    if buf.starts_with(&[0x1f, 0x8b]) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "input is gzipped; build with the gzip feature to read it",
+        ));
+    }
+
+    Ok(buf)
+}
+
+/// Errors from reading or parsing a softnet_stat file.
+#[derive(Debug)]
+pub enum SoftnetError {
+    /// The file could not be read.
+    Io(io::Error),
+
+    /// The input is not in the softnet_stat format.
+    Parse {
+        /// The parser that rejected the input.
+        kind: ErrorKind,
+        /// The byte offset into the input where parsing failed.
+        offset: usize,
+        /// The line where parsing failed, counting from 1.
+        line: usize,
+        /// The byte of the line where parsing failed, counting from 1.
+        column: usize,
+        /// The byte that was rejected, or `None` at the end of the input.
+        found: Option<u8>,
+    },
+
+    /// The input ended part way through a line.
+    UnsupportedFormat(Needed),
+}
+
+impl fmt::Display for SoftnetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SoftnetError::Io(e) => write!(f, "{}", e),
+            SoftnetError::Parse {
+                kind,
+                line,
+                column,
+                found,
+                ..
+            } => {
+                write!(f, "parse error at line {}, column {}: ", line, column)?;
+                match found {
+                    None if *line == 1 && *column == 1 => write!(f, "the input is empty"),
+                    None => write!(f, "expected another field, but the input ended"),
+                    Some(b'\n' | b'\r') if *column == 1 => write!(f, "unexpected blank line"),
+                    Some(b'\n' | b'\r') => write!(f, "expected another field, but the line ended"),
+                    Some(byte) if *kind == ErrorKind::Eof => write!(
+                        f,
+                        "expected the end of the line, found `{}`",
+                        ascii::escape_default(*byte)
+                    ),
+                    Some(byte) => write!(
+                        f,
+                        "expected hex digit, found `{}`",
+                        ascii::escape_default(*byte)
+                    ),
+                }
+            }
+            SoftnetError::UnsupportedFormat(needed) => {
+                write!(f, "unsupported format, needed {:?} more input", needed)
+            }
+        }
+    }
+}
+
+impl error::Error for SoftnetError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            SoftnetError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for SoftnetError {
+    fn from(e: io::Error) -> SoftnetError {
+        SoftnetError::Io(e)
+    }
+}
+
+/// Parse every line of a softnet_stat file, failing unless the whole input is consumed.
+///
+/// Unlike [`parse_softnet_stats`], a malformed line part way through the input is an error rather
+/// than the end of the stats. Trailing whitespace after the last line is ignored.
+pub fn parse(input: &[u8]) -> Result<Vec<SoftnetStat>, SoftnetError> {
+    let stats = iter_softnet_stats(input).collect::<Result<Vec<_>, _>>()?;
+
+    if stats.is_empty() {
+        // Like parse_softnet_stats, at least one line is required.
+        return Err(SoftnetError::Parse {
+            kind: ErrorKind::Eof,
+            offset: 0,
+            line: 1,
+            column: 1,
+            found: None,
+        });
+    }
+
+    Ok(stats)
+}
+
+/// Parse a softnet_stat file one line at a time as it is read.
+///
+/// This accepts the same input as [`parse`], but without holding the whole file or every stat in
+/// memory. Reading stops after the first error, and blank lines are only allowed at the end.
+pub fn iter_softnet_stats<R>(reader: R) -> impl Iterator<Item = Result<SoftnetStat, SoftnetError>>
+where
+    R: io::BufRead,
+{
+    Lines {
+        reader,
+        line: vec![],
+        offset: 0,
+        number: 0,
+        done: false,
+    }
+}
+
+struct Lines<R> {
+    reader: R,
+    line: Vec<u8>,
+    /// The byte offset of the start of `line` in the input.
+    offset: usize,
+    /// The line number of `line`, counting from 1.
+    number: usize,
+    done: bool,
+}
+
+impl<R: io::BufRead> Lines<R> {
+    /// Read the next line into `line`, returning `false` at the end of the input.
+    fn read_line(&mut self) -> io::Result<bool> {
+        self.offset += self.line.len();
+        self.line.clear();
+        self.number += 1;
+
+        Ok(self.reader.read_until(b'\n', &mut self.line)? > 0)
+    }
+
+    fn next_stat(&mut self) -> Result<Option<SoftnetStat>, SoftnetError> {
+        if !self.read_line()? {
+            return Ok(None);
+        }
+
+        let error = match parse_softnet_line(&self.line) {
+            Ok((_, stat)) => return Ok(Some(stat)),
+            Err(e) => parse_error(e, self.offset, self.number, self.line.len()),
+        };
+
+        // Trailing blank lines are fine, but the first blank line is the error if anything but
+        // whitespace follows it.
+        if self.line.iter().all(u8::is_ascii_whitespace) {
+            while self.read_line()? {
+                if !self.line.iter().all(u8::is_ascii_whitespace) {
+                    return Err(error);
+                }
+            }
+            return Ok(None);
+        }
+
+        Err(error)
+    }
+}
+
+impl<R: io::BufRead> Iterator for Lines<R> {
+    type Item = Result<SoftnetStat, SoftnetError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let next = self.next_stat().transpose();
+        if !matches!(next, Some(Ok(_))) {
+            self.done = true;
+        }
+
+        next
+    }
+}
+
+/// Convert an error from parsing line `number`, which is `len` bytes long and starts at `offset` in
+/// the input.
+fn parse_error(e: Err<Error<&[u8]>>, offset: usize, number: usize, len: usize) -> SoftnetError {
+    match e {
+        Err::Incomplete(needed) => SoftnetError::UnsupportedFormat(needed),
+        Err::Error(e) | Err::Failure(e) => {
+            let column = len - e.input.len();
+
+            SoftnetError::Parse {
+                kind: e.code,
+                offset: offset + column,
+                line: number,
+                column: column + 1,
+                found: e.input.first().copied(),
+            }
+        }
+    }
+}
+
+/// Parse every line of a softnet_stat file.
+pub fn parse_softnet_stats(input: &[u8]) -> IResult<&[u8], Vec<SoftnetStat>> {
+    many1(parse_softnet_line)(input)
+}
+
+/// Parse a single line of a softnet_stat file, including its line ending if it has one.
+pub fn parse_softnet_line(input: &[u8]) -> IResult<&[u8], SoftnetStat> {
+    if input.as_bytes().is_empty() {
+        return Err(Err::Error(Error::new(input, ErrorKind::Eof)));
+    }
+
+    let line = tuple((
+        hex_u32,                       // processed
+        preceded(space, hex_u32),      // dropped
+        preceded(space, hex_u32),      // time_squeeze
+        preceded(space, hex_u32),      // throttled
+        preceded(space, hex_u32),      // fastroute_hit
+        preceded(space, hex_u32),      // fastroute_success
+        preceded(space, hex_u32),      // fastroute_defer
+        preceded(space, hex_u32),      // fastroute_deferred_out
+        preceded(space, hex_u32),      // cpu collision
+        opt(preceded(space, hex_u32)), // received_rps
+        opt(preceded(space, hex_u32)), // flow_limit_count
+        opt(preceded(space, hex_u32)), // backlog_len
+        opt(preceded(space, hex_u32)), // cpu_id
+        // Some tools strip the newline from the end of a capture.
+        alt((line_ending, eof)),
+    ));
+
+    let mut parser = map(line, |result| SoftnetStat {
+        processed: result.0,
+        dropped: result.1,
+        time_squeeze: result.2,
+        throttled: result.3,
+        fastroute_hit: result.4,
+        fastroute_success: result.5,
+        fastroute_defer: result.6,
+        fastroute_deferred_out: result.7,
+        cpu_collision: result.8,
+        received_rps: result.9,
+        flow_limit_count: result.10,
+        backlog_len: result.11,
+        cpu_id: result.12,
+    });
+
+    parser(input)
+}
+
+/// The separator between fields.
+///
+/// The kernel writes a single space, but runs of spaces and tabs are accepted so files that have
+/// had their whitespace normalized by other tools still parse.
+fn space(input: &[u8]) -> IResult<&[u8], &[u8]> {
+    is_a(" \t")(input)
+}
+
+/// The number of fields the kernel wrote on the line a stat was parsed from.
+///
+/// The optional fields are parsed in order, so each one present adds a field to the nine
+/// mandatory ones.
+pub fn field_count(stat: &SoftnetStat) -> usize {
+    let optional = [
+        stat.received_rps,
+        stat.flow_limit_count,
+        stat.backlog_len,
+        stat.cpu_id,
+    ];
+
+    9 + optional.iter().filter(|field| field.is_some()).count()
+}
+
+/// The range of kernel versions that write a softnet_stat file with a given set of fields.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum KernelEra {
+    /// Only the nine mandatory fields, written before v2.6.36.
+    Pre2_6_36,
+    /// Adds received_rps.
+    V2_6_36,
+    /// Adds flow_limit_count.
+    V3_11,
+    /// Adds backlog_len and cpu_id.
+    V5_10OrLater,
+}
+
+impl fmt::Display for KernelEra {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let versions = match self {
+            KernelEra::Pre2_6_36 => "before v2.6.36",
+            KernelEra::V2_6_36 => "v2.6.36 to v3.10",
+            KernelEra::V3_11 => "v3.11 to v5.9",
+            KernelEra::V5_10OrLater => "v5.10 or later",
+        };
+
+        write!(f, "{}", versions)
+    }
+}
+
+/// Guess which kernels could have written `stats` from the optional fields of the first line.
+///
+/// With no lines to go by, the oldest era is returned.
+pub fn infer_kernel_era(stats: &[SoftnetStat]) -> KernelEra {
+    match stats.first() {
+        Some(stat) if stat.backlog_len.is_some() => KernelEra::V5_10OrLater,
+        Some(stat) if stat.flow_limit_count.is_some() => KernelEra::V3_11,
+        Some(stat) if stat.received_rps.is_some() => KernelEra::V2_6_36,
+        _ => KernelEra::Pre2_6_36,
+    }
+}
+
+/// Find the first line with a different number of fields than the first line.
+///
+/// Every line is written by the same kernel, so a mismatch means the dump was truncated or
+/// garbled somewhere along the way.
+pub fn inconsistent_line(stats: &[SoftnetStat]) -> Option<usize> {
+    let expected = field_count(stats.first()?);
+
+    stats.iter().position(|stat| field_count(stat) != expected)
+}
+
+/// Add two counters, saturating at `u32::MAX` rather than wrapping.
+///
+/// Every sum across CPUs or lines goes through here, since the sum of counters that each fit in
+/// 32 bits need not.
+pub fn add_counters(a: u32, b: u32) -> u32 {
+    a.saturating_add(b)
+}
+
+/// Add two counters that a kernel may not report. A missing counter adds nothing, so the sum is
+/// only missing if both are.
+pub fn add_optional_counters(a: Option<u32>, b: Option<u32>) -> Option<u32> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(add_counters(a, b)),
+        (a, b) => a.or(b),
+    }
+}
+
+/// Divide `numerator` by `denominator`, or `None` if the quotient would be infinite or NaN, such as
+/// when nothing has happened yet to divide by.
+pub fn ratio(numerator: f64, denominator: f64) -> Option<f64> {
+    let quotient = numerator / denominator;
+
+    if quotient.is_finite() {
+        Some(quotient)
+    } else {
+        None
+    }
+}
+
+/// The CPU of the line at `index`, for kernels that do not report a cpu_id.
+///
+/// No machine has anywhere near `u32::MAX` CPUs, but the conversion saturates rather than
+/// truncating on targets where `usize` is wider.
+pub fn line_cpu(index: usize) -> u32 {
+    u32::try_from(index).unwrap_or(u32::MAX)
+}
+
+/// Sum the counters of every CPU into a single stat for the whole machine.
+///
+/// Sums saturate at `u32::MAX` rather than wrapping. The backlog length is a queue length rather
+/// than a counter, so the longest backlog is kept instead, and the total has no cpu_id.
+pub fn aggregate(stats: &[SoftnetStat]) -> SoftnetStat {
+    let sum = |field: fn(&SoftnetStat) -> u32| stats.iter().map(field).fold(0, add_counters);
+    let sum_optional = |field: fn(&SoftnetStat) -> Option<u32>| {
+        stats.iter().map(field).fold(None, add_optional_counters)
+    };
+
+    SoftnetStat {
+        processed: sum(|stat| stat.processed),
+        dropped: sum(|stat| stat.dropped),
+        time_squeeze: sum(|stat| stat.time_squeeze),
+        throttled: sum(|stat| stat.throttled),
+        fastroute_hit: sum(|stat| stat.fastroute_hit),
+        fastroute_success: sum(|stat| stat.fastroute_success),
+        fastroute_defer: sum(|stat| stat.fastroute_defer),
+        fastroute_deferred_out: sum(|stat| stat.fastroute_deferred_out),
+        cpu_collision: sum(|stat| stat.cpu_collision),
+        received_rps: sum_optional(|stat| stat.received_rps),
+        flow_limit_count: sum_optional(|stat| stat.flow_limit_count),
+        backlog_len: stats.iter().filter_map(|stat| stat.backlog_len).max(),
+        cpu_id: None,
+    }
+}
+
+/// The change in each counter of one CPU between two snapshots.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct SoftnetChange {
+    /// The change in network frames processed.
+    pub processed: i64,
+
+    /// The change in network frames dropped.
+    pub dropped: i64,
+
+    /// The change in times the `net_rx_action` loop was squeezed.
+    pub time_squeeze: i64,
+
+    /// The change in device lock collisions.
+    pub cpu_collision: i64,
+
+    /// The change in Inter-processor Interrupt wake ups, when both snapshots have them.
+    pub received_rps: Option<i64>,
+
+    /// The change in times the flow limit was reached, when both snapshots have them.
+    pub flow_limit_count: Option<i64>,
+
+    /// The backlog length of the new snapshot.
+    ///
+    /// This is the current length of a queue rather than a counter, so it has no change.
+    pub backlog_len: Option<u32>,
+}
+
+impl SoftnetChange {
+    /// Whether any counter went down, which means it was reset, usually by a reboot between the
+    /// snapshots, and the changes are not meaningful.
+    pub fn reset(&self) -> bool {
+        [
+            self.processed,
+            self.dropped,
+            self.time_squeeze,
+            self.cpu_collision,
+        ]
+        .iter()
+        .chain(self.received_rps.iter())
+        .chain(self.flow_limit_count.iter())
+        .any(|change| *change < 0)
+    }
+}
+
+/// One CPU of two snapshots compared by [`diff`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub enum SoftnetDelta {
+    /// The CPU is in both snapshots.
+    Changed {
+        /// The cpu_id of the CPU, or its line index if the snapshots do not have cpu_ids.
+        cpu: u32,
+        /// The change in each counter.
+        change: SoftnetChange,
+    },
+
+    /// The CPU is only in the old snapshot, for example because it has gone offline since.
+    Removed {
+        /// The cpu_id of the CPU, or its line index if the snapshots do not have cpu_ids.
+        cpu: u32,
+    },
+
+    /// The CPU is only in the new snapshot.
+    Added {
+        /// The cpu_id of the CPU, or its line index if the snapshots do not have cpu_ids.
+        cpu: u32,
+    },
+}
+
+impl SoftnetDelta {
+    /// The cpu_id of the CPU, or its line index if the snapshots do not have cpu_ids.
+    pub fn cpu(&self) -> u32 {
+        match self {
+            SoftnetDelta::Changed { cpu, .. }
+            | SoftnetDelta::Removed { cpu }
+            | SoftnetDelta::Added { cpu } => *cpu,
+        }
+    }
+}
+
+/// Compare two snapshots of softnet_stat, CPU by CPU, in order of CPU.
+///
+/// CPUs are matched by cpu_id when every line of both snapshots has one. Otherwise they are matched
+/// by line index, which is only right if the same CPUs were online for both snapshots.
+///
+/// Unlike [`rate`], a counter that is lower in `new` than in `old` is not assumed to have wrapped,
+/// as snapshots taken far apart are more likely to straddle a reboot. The change is negative, and
+/// [`SoftnetChange::reset`] reports it.
+pub fn diff(old: &[SoftnetStat], new: &[SoftnetStat]) -> Vec<SoftnetDelta> {
+    let by_cpu_id = old.iter().chain(new).all(|stat| stat.cpu_id.is_some());
+    let keyed = |stats: &[SoftnetStat]| -> Vec<u32> {
+        stats
+            .iter()
+            .enumerate()
+            .map(|(index, stat)| match stat.cpu_id {
+                Some(cpu_id) if by_cpu_id => cpu_id,
+                _ => line_cpu(index),
+            })
+            .collect()
+    };
+
+    let mut cpus: BTreeMap<u32, (Option<&SoftnetStat>, Option<&SoftnetStat>)> = BTreeMap::new();
+    for (cpu, stat) in keyed(old).into_iter().zip(old) {
+        cpus.entry(cpu).or_default().0 = Some(stat);
+    }
+    for (cpu, stat) in keyed(new).into_iter().zip(new) {
+        cpus.entry(cpu).or_default().1 = Some(stat);
+    }
+
+    let change = |old: u32, new: u32| i64::from(new) - i64::from(old);
+    let optional = |old: Option<u32>, new: Option<u32>| Some(change(old?, new?));
+
+    cpus.into_iter()
+        .filter_map(|(cpu, stats)| match stats {
+            (Some(old), Some(new)) => Some(SoftnetDelta::Changed {
+                cpu,
+                change: SoftnetChange {
+                    processed: change(old.processed, new.processed),
+                    dropped: change(old.dropped, new.dropped),
+                    time_squeeze: change(old.time_squeeze, new.time_squeeze),
+                    cpu_collision: change(old.cpu_collision, new.cpu_collision),
+                    received_rps: optional(old.received_rps, new.received_rps),
+                    flow_limit_count: optional(old.flow_limit_count, new.flow_limit_count),
+                    backlog_len: new.backlog_len,
+                },
+            }),
+            (Some(_), None) => Some(SoftnetDelta::Removed { cpu }),
+            (None, Some(_)) => Some(SoftnetDelta::Added { cpu }),
+            (None, None) => None,
+        })
+        .collect()
+}
+
+#[test]
+fn test_parse_softnet_empty_line() {
+    let raw = b"";
+
+    // FIXME
+    // Err(Err::Error((&raw[..] ErrorKind::Eof)))) should work, but there is some type inference
+    // issue going on
+    assert!(parse_softnet_line(&raw[..]).is_err());
+}
+
+#[test]
+fn test_parse_softnet_line() {
+    let raw = b"6dcad223 00000000 00000001 00000000 00000000 00000000 00000000 00000000 00000000\n";
+
+    let (remaining, value) = parse_softnet_line(&raw[..]).unwrap();
+
+    assert_eq!(0, remaining.as_bytes().len());
+    assert_eq!(
+        SoftnetStat {
+            processed: 1842008611,
+            dropped: 0,
+            time_squeeze: 1,
+            throttled: 0,
+            fastroute_hit: 0,
+            fastroute_success: 0,
+            fastroute_defer: 0,
+            fastroute_deferred_out: 0,
+            cpu_collision: 0,
+            received_rps: None,
+            flow_limit_count: None,
+            backlog_len: None,
+            cpu_id: None,
+        },
+        value
+    );
+}
+
+#[test]
+fn test_parse_softnet_line_reserved() {
+    let raw = b"6dcad223 00000000 00000001 00000002 00000003 00000004 00000005 00000006 00000007\n";
+
+    let (_, value) = parse_softnet_line(&raw[..]).unwrap();
+
+    assert_eq!(2, value.throttled);
+    assert_eq!(3, value.fastroute_hit);
+    assert_eq!(4, value.fastroute_success);
+    assert_eq!(5, value.fastroute_defer);
+    assert_eq!(6, value.fastroute_deferred_out);
+    assert_eq!(7, value.cpu_collision);
+}
+
+#[test]
+fn test_parse_softnet_stats() {
+    let pwd = env!("CARGO_MANIFEST_DIR");
+    let files = [
+        format!("{}/tests/proc-net-softnet_stat-2_6_32", pwd),
+        format!("{}/tests/proc-net-softnet_stat-2_6_36", pwd),
+        format!("{}/tests/proc-net-softnet_stat-3_11", pwd),
+        format!("{}/tests/proc-net-softnet_stat-4_19", pwd),
+        format!("{}/tests/proc-net-softnet_stat-5_10_47", pwd),
+        format!("{}/tests/proc-net-softnet_stat-5_10_47-single_cpu", pwd),
+        format!("{}/tests/proc-net-softnet_stat-5_10_47-drops", pwd),
+    ];
+
+    for file in files.iter() {
+        let handle = std::fs::File::open(file).unwrap();
+        let raw = read_proc_file(handle).unwrap();
+
+        let (_, stats) = parse_softnet_stats(&raw).unwrap();
+        assert_eq!(None, inconsistent_line(&stats));
+    }
+}
+
+#[test]
+fn test_parse_softnet_stats_whitespace() {
+    let pwd = env!("CARGO_MANIFEST_DIR");
+    let read = |name: &str| {
+        let handle = std::fs::File::open(format!("{}/tests/{}", pwd, name)).unwrap();
+        parse(&read_proc_file(handle).unwrap()).unwrap()
+    };
+
+    let expected = read("proc-net-softnet_stat-5_10_47");
+    assert_eq!(expected, read("proc-net-softnet_stat-5_10_47-tabs"));
+    assert_eq!(expected, read("proc-net-softnet_stat-5_10_47-double_space"));
+
+    // A line break is not a separator, even when it is surrounded by ones that are.
+    let raw =
+        b"6dcad223 00000000 00000001 00000000\t\n00000000 00000000 00000000 00000000 00000000\n";
+    assert!(parse_softnet_line(&raw[..]).is_err());
+}
+
+#[test]
+fn test_parse_softnet_stats_4_19() {
+    let pwd = env!("CARGO_MANIFEST_DIR");
+    let handle = std::fs::File::open(format!("{}/tests/proc-net-softnet_stat-4_19", pwd)).unwrap();
+    let stats = parse(&read_proc_file(handle).unwrap()).unwrap();
+
+    // cpu_collision was removed in v4.7, but its slot is still written as zero, so the layout is
+    // the same as v3.11.
+    assert_eq!(4, stats.len());
+    assert_eq!(11, field_count(&stats[1]));
+    assert_eq!(0x09e1d4c7, stats[1].processed);
+    assert_eq!(3, stats[1].dropped);
+    assert_eq!(0x172, stats[1].time_squeeze);
+    assert_eq!(0, stats[1].cpu_collision);
+    assert_eq!(Some(0), stats[1].received_rps);
+    assert_eq!(Some(0), stats[1].flow_limit_count);
+    assert_eq!(None, stats[1].backlog_len);
+}
+
+#[test]
+fn test_infer_kernel_era() {
+    let pwd = env!("CARGO_MANIFEST_DIR");
+    let era = |name: &str| {
+        let handle = std::fs::File::open(format!("{}/tests/{}", pwd, name)).unwrap();
+        infer_kernel_era(&parse(&read_proc_file(handle).unwrap()).unwrap())
+    };
+
+    assert_eq!(KernelEra::Pre2_6_36, era("proc-net-softnet_stat-2_6_32"));
+    assert_eq!(KernelEra::V2_6_36, era("proc-net-softnet_stat-2_6_36"));
+    assert_eq!(KernelEra::V3_11, era("proc-net-softnet_stat-3_11"));
+    assert_eq!(KernelEra::V3_11, era("proc-net-softnet_stat-4_19"));
+    for name in [
+        "proc-net-softnet_stat-5_10_47",
+        #[cfg(feature = "gzip")]
+        "proc-net-softnet_stat-5_10_47-32_cpu-gzip",
+        "proc-net-softnet_stat-5_10_47-double_space",
+        "proc-net-softnet_stat-5_10_47-drops",
+        "proc-net-softnet_stat-5_10_47-single_cpu",
+        "proc-net-softnet_stat-5_10_47-tabs",
+    ] {
+        assert_eq!(KernelEra::V5_10OrLater, era(name), "{}", name);
+    }
+
+    assert_eq!(KernelEra::Pre2_6_36, infer_kernel_era(&[]));
+    assert_eq!("v3.11 to v5.9", KernelEra::V3_11.to_string());
+}
+
+#[test]
+fn test_inconsistent_line() {
+    let raw =
+        b"6dcad223 00000000 00000001 00000000 00000000 00000000 00000000 00000000 00000000 00000000
+6f0e1565 00000000 00000002 00000000 00000000 00000000 00000000 00000000 00000000 00000000
+660774ec 00000000 00000003 00000000 00000000 00000000 00000000 00000000 00000000
+";
+
+    let (_, stats) = parse_softnet_stats(&raw[..]).unwrap();
+
+    assert_eq!(Some(2), inconsistent_line(&stats));
+    assert_eq!(None, inconsistent_line(&stats[..2]));
+    assert_eq!(None, inconsistent_line(&[]));
+}
+
+#[test]
+fn test_parse_without_trailing_newline() {
+    let raw = b"6dcad223 00000000 00000001 00000000 00000000 00000000 00000000 00000000 00000000
+6f0e1565 00000000 00000002 00000000 00000000 00000000 00000000 00000000 00000000";
+
+    let (remaining, stats) = parse_softnet_stats(&raw[..]).unwrap();
+    assert_eq!(0, remaining.len());
+    assert_eq!(2, stats.len());
+    assert_eq!(0x6f0e1565, stats[1].processed);
+    assert_eq!(2, stats[1].time_squeeze);
+
+    assert_eq!(stats, parse(&raw[..]).unwrap());
+}
+
+#[test]
+fn test_parse() {
+    let raw =
+        b"6dcad223 00000000 00000001 00000000 00000000 00000000 00000000 00000000 00000000\n\n";
+
+    let stats = parse(&raw[..]).unwrap();
+    assert_eq!(1, stats.len());
+}
+
+#[test]
+fn test_parse_garbage() {
+    let raw = b"6dcad223 00000000 00000001 00000000 00000000 00000000 00000000 00000000 00000000
+6dcad223 00000000 00000001 garbage!
+";
+
+    match parse(&raw[..]) {
+        Err(e @ SoftnetError::Parse { .. }) => {
+            assert_eq!(
+                "parse error at line 2, column 28: expected hex digit, found `g`",
+                e.to_string()
+            );
+            match e {
+                SoftnetError::Parse {
+                    kind,
+                    offset,
+                    line,
+                    column,
+                    found,
+                } => {
+                    assert_eq!(ErrorKind::IsA, kind);
+                    assert_eq!(108, offset);
+                    assert_eq!((2, 28), (line, column));
+                    assert_eq!(Some(b'g'), found);
+                }
+                _ => unreachable!(),
+            }
+        }
+        other => panic!("expected a parse error, got {:?}", other),
+    }
+
+    assert!(matches!(
+        parse(b"not a softnet_stat file"),
+        Err(SoftnetError::Parse { offset: 0, .. })
+    ));
+    assert!(matches!(
+        parse(b""),
+        Err(SoftnetError::Parse {
+            kind: ErrorKind::Eof,
+            ..
+        })
+    ));
+}
+
+#[test]
+fn test_parse_error_messages() {
+    let message = |raw: &[u8]| parse(raw).unwrap_err().to_string();
+    let line = "6dcad223 00000000 00000001 00000000 00000000 00000000 00000000 00000000 00000000";
+
+    assert_eq!(
+        "parse error at line 1, column 1: the input is empty",
+        message(b"")
+    );
+    assert_eq!(
+        "parse error at line 2, column 18: expected another field, but the line ended",
+        message(format!("{}\n6dcad223 00000000\n", line).as_bytes())
+    );
+    assert_eq!(
+        "parse error at line 1, column 18: expected another field, but the input ended",
+        message(b"6dcad223 00000000")
+    );
+    assert_eq!(
+        "parse error at line 2, column 1: unexpected blank line",
+        message(format!("{}\n\n{}\n", line, line).as_bytes())
+    );
+    assert_eq!(
+        "parse error at line 1, column 81: expected the end of the line, found `,`",
+        message(format!("{},\n", line).as_bytes())
+    );
+}
+
+#[test]
+fn test_rate() {
+    let prev = SoftnetStat {
+        processed: 1000,
+        dropped: 0,
+        time_squeeze: 4,
+        throttled: 0,
+        fastroute_hit: 0,
+        fastroute_success: 0,
+        fastroute_defer: 0,
+        fastroute_deferred_out: 0,
+        cpu_collision: 0,
+        received_rps: Some(10),
+        flow_limit_count: None,
+        backlog_len: Some(7),
+        cpu_id: Some(1),
+    };
+    let cur = SoftnetStat {
+        processed: 3000,
+        time_squeeze: 6,
+        received_rps: Some(30),
+        flow_limit_count: Some(5),
+        backlog_len: Some(2),
+        ..prev.clone()
+    };
+
+    assert_eq!(
+        SoftnetRate {
+            processed: 1000.0,
+            dropped: 0.0,
+            time_squeeze: 1.0,
+            cpu_collision: 0.0,
+            received_rps: Some(10.0),
+            flow_limit_count: None,
+            backlog_len: Some(2),
+            cpu_id: Some(1),
+        },
+        rate(&prev, &cur, 2.0)
+    );
+}
+
+#[test]
+fn test_new() {
+    assert_eq!(SoftnetStat::default(), SoftnetStat::new(0, 0, 0));
+
+    let (_, stats) = parse_softnet_stats(
+        b"6dcad223 00000000 00000001 00000000 00000000 00000000 00000000 00000000 00000007\n",
+    )
+    .unwrap();
+    assert_eq!(
+        stats[0],
+        SoftnetStat::new(0x6dcad223, 0, 1).with_cpu_collision(7)
+    );
+    assert_eq!(None, stats[0].received_rps);
+}
+
+#[test]
+fn test_rate_wraps_around() {
+    let prev = SoftnetStat {
+        processed: u32::MAX - 9,
+        dropped: u32::MAX,
+        time_squeeze: 0,
+        throttled: 0,
+        fastroute_hit: 0,
+        fastroute_success: 0,
+        fastroute_defer: 0,
+        fastroute_deferred_out: 0,
+        cpu_collision: 0,
+        received_rps: Some(u32::MAX - 1),
+        flow_limit_count: None,
+        backlog_len: None,
+        cpu_id: None,
+    };
+    let cur = SoftnetStat {
+        processed: 10,
+        dropped: 0,
+        received_rps: Some(0),
+        ..prev.clone()
+    };
+
+    let rate = rate(&prev, &cur, 1.0);
+    assert_eq!(20.0, rate.processed);
+    assert_eq!(1.0, rate.dropped);
+    assert_eq!(Some(2.0), rate.received_rps);
+}
+
+#[test]
+fn test_rate_no_time() {
+    let stat = SoftnetStat::new(u32::MAX, u32::MAX, u32::MAX).with_received_rps(1);
+
+    let rate = rate(&SoftnetStat::default().with_received_rps(0), &stat, 0.0);
+    assert_eq!(0.0, rate.processed);
+    assert_eq!(0.0, rate.dropped);
+    assert_eq!(Some(0.0), rate.received_rps);
+}
+
+#[test]
+fn test_counter_arithmetic() {
+    assert_eq!(u32::MAX, add_counters(u32::MAX, 1));
+    assert_eq!(u32::MAX, add_counters(u32::MAX, u32::MAX));
+    assert_eq!(3, add_counters(1, 2));
+
+    assert_eq!(
+        Some(u32::MAX),
+        add_optional_counters(Some(u32::MAX), Some(1))
+    );
+    assert_eq!(Some(1), add_optional_counters(None, Some(1)));
+    assert_eq!(None, add_optional_counters(None, None));
+
+    assert_eq!(Some(0.5), ratio(1.0, 2.0));
+    assert_eq!(None, ratio(1.0, 0.0));
+    assert_eq!(None, ratio(0.0, 0.0));
+    assert_eq!(Some(1.0), ratio(f64::from(u32::MAX), f64::from(u32::MAX)));
+
+    assert_eq!(7, line_cpu(7));
+    assert_eq!(u32::MAX, line_cpu(usize::MAX));
+}
+
+#[test]
+fn test_aggregate() {
+    let raw = b"00000001 00000000 00000002 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000005 00000000
+fffffffe 00000001 00000003 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000002 00000001
+00000002 00000001 00000004 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000009 00000002
+";
+    let (_, stats) = parse_softnet_stats(&raw[..]).unwrap();
+
+    let total = aggregate(&stats);
+    // 1 + 0xfffffffe + 2 would overflow, so the sum saturates.
+    assert_eq!(u32::MAX, total.processed);
+    assert_eq!(2, total.dropped);
+    assert_eq!(9, total.time_squeeze);
+    assert_eq!(Some(0), total.received_rps);
+    assert_eq!(Some(9), total.backlog_len);
+    assert_eq!(None, total.cpu_id);
+
+    let (_, old) = parse_softnet_stats(
+        b"00000001 00000000 00000002 00000000 00000000 00000000 00000000 00000000 00000000\n",
+    )
+    .unwrap();
+    assert_eq!(None, aggregate(&old).received_rps);
+    assert_eq!(None, aggregate(&old).backlog_len);
+}
+
+#[test]
+fn test_diff() {
+    let (_, old) = parse_softnet_stats(
+        b"00000010 00000000 00000001 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000
+00000020 00000001 00000002 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000003 00000001
+",
+    )
+    .unwrap();
+    let (_, new) = parse_softnet_stats(
+        b"00000018 00000000 00000004 00000000 00000000 00000000 00000000 00000000 00000000 00000002 00000000 00000000 00000000
+00000030 00000003 00000002 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000001 00000001
+",
+    )
+    .unwrap();
+
+    assert_eq!(
+        vec![
+            SoftnetDelta::Changed {
+                cpu: 0,
+                change: SoftnetChange {
+                    processed: 8,
+                    dropped: 0,
+                    time_squeeze: 3,
+                    cpu_collision: 0,
+                    received_rps: Some(2),
+                    flow_limit_count: Some(0),
+                    backlog_len: Some(0),
+                },
+            },
+            SoftnetDelta::Changed {
+                cpu: 1,
+                change: SoftnetChange {
+                    processed: 16,
+                    dropped: 2,
+                    time_squeeze: 0,
+                    cpu_collision: 0,
+                    received_rps: Some(0),
+                    flow_limit_count: Some(0),
+                    backlog_len: Some(1),
+                },
+            },
+        ],
+        diff(&old, &new)
+    );
+    assert!(diff(&old, &new).iter().all(|delta| match delta {
+        SoftnetDelta::Changed { change, .. } => !change.reset(),
+        _ => false,
+    }));
+}
+
+#[test]
+fn test_diff_reset() {
+    let (_, old) = parse_softnet_stats(
+        b"ffffff00 00000005 00000001 00000000 00000000 00000000 00000000 00000000 00000000\n",
+    )
+    .unwrap();
+    let (_, new) = parse_softnet_stats(
+        b"00000100 00000000 00000001 00000000 00000000 00000000 00000000 00000000 00000000\n",
+    )
+    .unwrap();
+
+    let deltas = diff(&old, &new);
+    match &deltas[..] {
+        [SoftnetDelta::Changed { cpu: 0, change }] => {
+            // Not wrapped around to a huge positive change.
+            assert_eq!(256 - 0xffffff00, change.processed);
+            assert_eq!(-5, change.dropped);
+            assert!(change.reset());
+        }
+        _ => panic!("unexpected deltas {:?}", deltas),
+    }
+}
+
+#[test]
+fn test_diff_missing_cpu() {
+    // cpu 1 went offline and cpu 3 came online between the snapshots.
+    let (_, old) = parse_softnet_stats(
+        b"00000001 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000
+00000001 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000001
+00000001 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000002
+",
+    )
+    .unwrap();
+    let (_, new) = parse_softnet_stats(
+        b"00000002 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000
+00000002 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000002
+00000002 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000003
+",
+    )
+    .unwrap();
+
+    let deltas = diff(&old, &new);
+    assert_eq!(
+        vec![0, 1, 2, 3],
+        deltas.iter().map(SoftnetDelta::cpu).collect::<Vec<_>>()
+    );
+    assert!(matches!(deltas[0], SoftnetDelta::Changed { .. }));
+    assert_eq!(SoftnetDelta::Removed { cpu: 1 }, deltas[1]);
+    assert!(matches!(deltas[2], SoftnetDelta::Changed { .. }));
+    assert_eq!(SoftnetDelta::Added { cpu: 3 }, deltas[3]);
+
+    // Without cpu_ids, the lines are matched by index.
+    let (_, new) = parse_softnet_stats(
+        b"00000002 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000\n",
+    )
+    .unwrap();
+    let deltas = diff(&old, &new);
+    assert_eq!(3, deltas.len());
+    assert!(matches!(deltas[0], SoftnetDelta::Changed { cpu: 0, .. }));
+    assert_eq!(SoftnetDelta::Removed { cpu: 2 }, deltas[2]);
+}
+
+#[cfg(feature = "gzip")]
+#[test]
+fn test_read_proc_file_gzip() {
+    let pwd = env!("CARGO_MANIFEST_DIR");
+    let raw = std::fs::read(format!("{}/tests/proc-net-softnet_stat-5_10_47-drops", pwd)).unwrap();
+
+    // Gzip the fixture in a single stored block, which needs no compressor.
+    let mut compressed = vec![0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03];
+    compressed.push(0x01);
+    compressed.extend_from_slice(&(raw.len() as u16).to_le_bytes());
+    compressed.extend_from_slice(&(!(raw.len() as u16)).to_le_bytes());
+    compressed.extend_from_slice(&raw);
+    compressed.extend_from_slice(&gzip::crc32(&raw).to_le_bytes());
+    compressed.extend_from_slice(&(raw.len() as u32).to_le_bytes());
+
+    let decompressed = read_proc_file(&compressed[..]).unwrap();
+    assert_eq!(raw, decompressed);
+    assert_eq!(parse(&raw).unwrap(), parse(&decompressed).unwrap());
+
+    compressed.truncate(compressed.len() - 1);
+    let e = read_proc_file(&compressed[..]).unwrap_err();
+    assert_eq!(io::ErrorKind::InvalidData, e.kind());
+}
+
+#[cfg(not(feature = "gzip"))]
+#[test]
+fn test_read_proc_file_gzip_disabled() {
+    let pwd = env!("CARGO_MANIFEST_DIR");
+    let handle = std::fs::File::open(format!(
+        "{}/tests/proc-net-softnet_stat-5_10_47-32_cpu-gzip",
+        pwd
+    ))
+    .unwrap();
+
+    let e = read_proc_file(handle).unwrap_err();
+    assert_eq!(io::ErrorKind::InvalidData, e.kind());
+}
+
+#[test]
+fn test_iter_softnet_stats() {
+    let pwd = env!("CARGO_MANIFEST_DIR");
+    let path = format!("{}/tests/proc-net-softnet_stat-5_10_47", pwd);
+
+    let raw = read_proc_file(std::fs::File::open(&path).unwrap()).unwrap();
+    let (_, expected) = parse_softnet_stats(&raw).unwrap();
+
+    let reader = io::BufReader::new(std::fs::File::open(&path).unwrap());
+    let stats: Vec<SoftnetStat> = iter_softnet_stats(reader).map(Result::unwrap).collect();
+    assert_eq!(expected, stats);
+}
+
+#[test]
+fn test_iter_softnet_stats_error() {
+    let raw = b"6dcad223 00000000 00000001 00000000 00000000 00000000 00000000 00000000 00000000
+
+6dcad223 00000000 00000001 00000000 00000000 00000000 00000000 00000000 00000000
+";
+
+    let mut stats = iter_softnet_stats(&raw[..]);
+    assert!(matches!(stats.next(), Some(Ok(_))));
+    assert!(matches!(
+        stats.next(),
+        Some(Err(SoftnetError::Parse { offset: 81, .. }))
+    ));
+    // Nothing more is read after an error.
+    assert!(stats.next().is_none());
+}
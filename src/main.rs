@@ -15,302 +15,3518 @@
  *  along with this program.  If not, see <http://www.gnu.org/licenses/>.
 */
 
+use std::cmp::Ordering;
+use std::collections::HashMap;
 use std::env;
-use std::fs::File;
-use std::io;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::net::TcpListener;
+use std::ops::RangeInclusive;
+use std::path::Path;
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use getopts::Options;
-use nom::character::complete::{char, line_ending};
-use nom::combinator::{map, opt};
-use nom::error::{Error, ErrorKind};
-use nom::multi::many1;
-use nom::number::complete::hex_u32;
-use nom::sequence::{preceded, tuple};
-use nom::{AsBytes, Err, IResult};
 use serde_derive::{Deserialize, Serialize};
+use softnet_stat::{
+    add_counters, add_optional_counters, aggregate, diff, field_count, inconsistent_line,
+    infer_kernel_era, line_cpu, parse_softnet_stats, rate, ratio, read_proc_file, SoftnetDelta,
+    SoftnetRate, SoftnetStat,
+};
 
-/// Network data processing statistics
-#[derive(Debug, Eq, PartialEq, Serialize, Deserialize)]
-struct SoftnetStat {
-    /// The number of network frames processed.
-    ///
-    /// This can be more than the total number of network frames received if
-    /// you are using ethernet bonding. There are cases where the ethernet
-    /// bonding driver will trigger network data to be re-processed, which
-    /// would increment the processed count more than once for the same packet.
-    pub processed: u32,
-
-    /// The number of network frames dropped because there was no room on the processing queue.
-    pub dropped: u32,
-
-    /// The number of times the `net_rx_action` loop terminated because the budget was consumed or
-    /// the time limit was reached, but more work could have been.
-    pub time_squeeze: u32,
-
-    /// The number of times a collision occurred when trying to obtain a device lock
-    /// when transmitting packets.
-    ///
-    /// This was removed in kernel v4.7
-    pub cpu_collision: u32,
-
-    /// The number of times this CPU has been woken up to process packets via an Inter-processor Interrupt.
-    ///
-    /// Support was added in kernel v2.6.36
-    pub received_rps: Option<u32>,
-
-    /// The number of times the flow limit has been reached.
-    ///
-    /// Flow limiting is an optional Receive Packet Steering feature.
-    ///
-    /// Support was added in kernel v3.11
-    pub flow_limit_count: Option<u32>,
-
-    /// The network backlog length.
-    ///
-    /// Support was added in kernel v5.10
-    pub backlog_len: Option<u32>,
-
-    /// The cpu_id is the CPU id owning this softnet data.
-    ///
-    /// There is not a direct match between softnet_stat
-    /// lines and the related CPU. Offline CPUs are not dumped.
-    ///
-    /// Support was added in kernel v5.10
-    pub cpu_id: Option<u32>,
+mod color;
+mod interrupt;
+mod keys;
+mod serve;
+#[cfg(feature = "yaml")]
+mod yaml;
+
+/// Documentation for a field of `SoftnetStat`, included in json output by `--with-metadata`.
+#[derive(Debug, Serialize)]
+struct FieldMeta {
+    name: &'static str,
+    description: &'static str,
+
+    /// The kernel version that added the field, if it has not always been there.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    since: Option<&'static str>,
+
+    /// The kernel version that stopped reporting the field.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    removed: Option<&'static str>,
+}
+
+/// Kernel support for each field, mirroring the doc comments on `SoftnetStat`.
+const FIELDS: &[FieldMeta] = &[
+    FieldMeta {
+        name: "processed",
+        description: "The number of network frames processed.",
+        since: None,
+        removed: None,
+    },
+    FieldMeta {
+        name: "dropped",
+        description:
+            "The number of network frames dropped because there was no room on the processing queue.",
+        since: None,
+        removed: None,
+    },
+    FieldMeta {
+        name: "time_squeeze",
+        description: "The number of times the net_rx_action loop terminated because the budget was consumed or the time limit was reached, but more work could have been.",
+        since: None,
+        removed: None,
+    },
+    FieldMeta {
+        name: "throttled",
+        description: "The number of times the backlog queue was throttled. Reserved and always zero on current kernels.",
+        since: None,
+        removed: None,
+    },
+    FieldMeta {
+        name: "fastroute_hit",
+        description: "The number of frames routed by the removed fast route feature. Reserved and always zero.",
+        since: None,
+        removed: None,
+    },
+    FieldMeta {
+        name: "fastroute_success",
+        description: "The number of frames the removed fast route feature handled successfully. Reserved and always zero.",
+        since: None,
+        removed: None,
+    },
+    FieldMeta {
+        name: "fastroute_defer",
+        description: "The number of frames the removed fast route feature deferred. Reserved and always zero.",
+        since: None,
+        removed: None,
+    },
+    FieldMeta {
+        name: "fastroute_deferred_out",
+        description: "The number of deferred frames the removed fast route feature sent out. Reserved and always zero.",
+        since: None,
+        removed: None,
+    },
+    FieldMeta {
+        name: "cpu_collision",
+        description: "The number of times a collision occurred when trying to obtain a device lock when transmitting packets.",
+        since: None,
+        removed: Some("v4.7"),
+    },
+    FieldMeta {
+        name: "received_rps",
+        description: "The number of times this CPU has been woken up to process packets via an Inter-processor Interrupt.",
+        since: Some("v2.6.36"),
+        removed: None,
+    },
+    FieldMeta {
+        name: "flow_limit_count",
+        description: "The number of times the flow limit has been reached.",
+        since: Some("v3.11"),
+        removed: None,
+    },
+    FieldMeta {
+        name: "backlog_len",
+        description: "The network backlog length.",
+        since: Some("v5.10"),
+        removed: None,
+    },
+    FieldMeta {
+        name: "cpu_id",
+        description: "The CPU id owning this softnet data.",
+        since: Some("v5.10"),
+        removed: None,
+    },
+];
+
+/// The json document written by `--with-metadata`.
+#[derive(Serialize)]
+struct MetadataReport<'a> {
+    meta: Meta,
+    stats: Vec<JsonRow<'a>>,
+}
+
+/// The json document written by `--timestamp`.
+#[derive(Serialize)]
+struct SoftnetReport<'a> {
+    /// When the stats were read, in RFC 3339 format.
+    timestamp: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    meta: Option<Meta>,
+    stats: Vec<JsonRow<'a>>,
+}
+
+/// The json or yaml document for a snapshot, wrapped in a report when it has metadata or a
+/// timestamp.
+#[derive(Serialize)]
+#[serde(untagged)]
+enum Document<'a> {
+    Stats(Vec<JsonRow<'a>>),
+    Metadata(MetadataReport<'a>),
+    Timestamped(SoftnetReport<'a>),
+}
+
+/// A stat or rate as written in json output.
+#[derive(Serialize)]
+struct JsonRow<'a, T = SoftnetStat> {
+    /// The cpu of the row, for `--ndjson` where each stat is written on its own.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cpu: Option<u32>,
+    #[serde(flatten)]
+    stat: &'a T,
+}
+
+#[derive(Serialize)]
+struct Meta {
+    fields: &'static [FieldMeta],
+}
+
+/// A parsed line of the softnet_stat file along with where it appeared in the file.
+struct Row {
+    /// The zero-based position of the line in the file.
+    index: usize,
+    stat: SoftnetStat,
+}
+
+impl Row {
+    /// The CPU this row describes.
+    fn cpu(&self) -> u32 {
+        // Prior to Linux kernel v5.10, we used the index to determine the CPU Id. However, this is
+        // not always correct as offline CPUs are not reported in the softnet data. If we are on a
+        // Linux kernel that supports the cpu_id data, then we use that instead.
+        self.stat.cpu_id.unwrap_or_else(|| line_cpu(self.index))
+    }
+}
+
+/// Pair each parsed stat with its position in the file.
+fn into_rows(stats: Vec<SoftnetStat>) -> Vec<Row> {
+    stats
+        .into_iter()
+        .enumerate()
+        .map(|(index, stat)| Row { index, stat })
+        .collect()
+}
+
+/// The rate of a CPU's counters between two samples, printed by `--rate`.
+struct RateRow {
+    /// The zero-based position of the line in the later sample.
+    index: usize,
+    rate: SoftnetRate,
+}
+
+/// The rate of each row in `cur` since the row for the same CPU in `prev`.
+///
+/// A CPU that was not in the earlier sample, because it has just come online, has no rate yet and
+/// is left out.
+fn rates(prev: &[Row], cur: &[Row], secs: f64) -> Vec<RateRow> {
+    let prev: HashMap<u32, &SoftnetStat> = prev.iter().map(|row| (row.cpu(), &row.stat)).collect();
+
+    cur.iter()
+        .filter_map(|row| {
+            let before = prev.get(&row.cpu())?;
+            Some(RateRow {
+                index: row.index,
+                rate: rate(before, &row.stat, secs),
+            })
+        })
+        .collect()
+}
+
+/// The supported output formats.
+#[derive(Clone, Copy)]
+enum Format {
+    Table,
+    Json,
+    Ndjson,
+    Csv,
+    Prometheus,
+    Statusline,
+    Influx,
+    Statsd,
+    Yaml,
+}
+
+impl Format {
+    fn write<W>(self, out: W, rows: &[Row], options: &OutputOptions) -> io::Result<()>
+    where
+        W: io::Write,
+    {
+        match self {
+            Format::Table => print(out, rows, options),
+            Format::Json => {
+                let timestamp = options.timestamp.then(|| rfc3339(SystemTime::now()));
+                json(
+                    out,
+                    rows,
+                    options.with_metadata,
+                    options.pretty,
+                    timestamp.as_deref(),
+                )
+            }
+            Format::Ndjson => ndjson(out, rows),
+            Format::Csv => csv(out, rows),
+            Format::Prometheus => prometheus(out, rows, options),
+            Format::Statusline => statusline(out, rows),
+            Format::Influx => {
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default();
+                influx(out, rows, options, now.as_nanos())
+            }
+            Format::Statsd => statsd(out, rows, options),
+            #[cfg(feature = "yaml")]
+            Format::Yaml => {
+                let timestamp = options.timestamp.then(|| rfc3339(SystemTime::now()));
+                yaml(out, rows, options.with_metadata, timestamp.as_deref())
+            }
+            #[cfg(not(feature = "yaml"))]
+            Format::Yaml => unreachable!("--yaml is rejected without the yaml feature"),
+        }
+    }
+
+    /// The file extension used when writing this format to a file.
+    fn extension(self) -> &'static str {
+        match self {
+            Format::Table => "txt",
+            Format::Json => "json",
+            Format::Ndjson => "ndjson",
+            Format::Csv => "csv",
+            Format::Prometheus => "prom",
+            Format::Statusline => "txt",
+            Format::Influx => "lp",
+            Format::Statsd => "statsd",
+            Format::Yaml => "yaml",
+        }
+    }
+}
+
+/// Settings that change how the output formats render rows.
+#[derive(Default)]
+struct OutputOptions {
+    cpu_label: CpuLabel,
+    metric_prefix: MetricPrefix,
+    with_metadata: bool,
+    pretty: bool,
+    timestamp: bool,
+    frames_per_squeeze: bool,
+    human: bool,
+    total: bool,
+    color: bool,
+    /// A fixed width for every column of the tables, instead of sizing them to their contents.
+    width: Option<usize>,
+}
+
+/// The label used to identify the CPU of each prometheus sample.
+struct CpuLabel {
+    name: String,
+    template: String,
+}
+
+impl Default for CpuLabel {
+    fn default() -> CpuLabel {
+        CpuLabel {
+            name: "cpu".to_string(),
+            template: "cpu{id}".to_string(),
+        }
+    }
+}
+
+impl CpuLabel {
+    /// Create a label named `name` whose value is `template` with `{id}` replaced by the CPU id.
+    fn new(name: &str, template: &str) -> Result<CpuLabel, String> {
+        if !is_valid_label_name(name) {
+            return Err(format!(
+                "`{}` is not a valid prometheus label name; it must match [a-zA-Z_][a-zA-Z0-9_]* and not start with __",
+                name
+            ));
+        }
+
+        if !template.contains("{id}") {
+            return Err(format!(
+                "cpu label format `{}` must contain `{{id}}`",
+                template
+            ));
+        }
+
+        Ok(CpuLabel {
+            name: name.to_string(),
+            template: template.to_string(),
+        })
+    }
+
+    /// The value of the label for the given CPU.
+    fn value(&self, cpu: u32) -> String {
+        self.template.replace("{id}", &cpu.to_string())
+    }
+
+    /// Render the label as `name="value"` for the given CPU.
+    fn render(&self, cpu: u32) -> String {
+        format!("{}=\"{}\"", self.name, escape_label_value(&self.value(cpu)))
+    }
+}
+
+/// Escape a prometheus label value so it can be written between double quotes.
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn is_valid_label_name(name: &str) -> bool {
+    !name.starts_with("__") && is_valid_name(name)
+}
+
+/// Whether `name` matches `[a-zA-Z_][a-zA-Z0-9_]*`, as prometheus metric and label names must.
+fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {}
+        _ => return false,
+    }
+
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// The prefix of every prometheus metric name, such as `softnet` in `softnet_frames_processed`.
+struct MetricPrefix(String);
+
+impl Default for MetricPrefix {
+    fn default() -> MetricPrefix {
+        MetricPrefix("softnet".to_string())
+    }
+}
+
+impl MetricPrefix {
+    fn new(prefix: &str) -> Result<MetricPrefix, String> {
+        if !is_valid_name(prefix) {
+            return Err(format!(
+                "`{}` is not a valid prometheus metric prefix; it must match [a-zA-Z_][a-zA-Z0-9_]*",
+                prefix
+            ));
+        }
+
+        Ok(MetricPrefix(prefix.to_string()))
+    }
+
+    /// The full name of the metric called `name` after the prefix.
+    fn name(&self, name: &str) -> String {
+        format!("{}_{}", self.0, name)
+    }
+}
+
+/// Per-CPU limits loaded by `--thresholds`.
+///
+/// ```json
+/// {"default": {"dropped": 0, "time_squeeze": 100}, "cpus": {"3": {"time_squeeze": 500}}}
+/// ```
+///
+/// A limit missing from a CPU's entry falls back to the default, and a limit missing from both is
+/// not checked.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Thresholds {
+    #[serde(default)]
+    default: Limits,
+    #[serde(default)]
+    cpus: HashMap<u32, Limits>,
+}
+
+/// The maximum acceptable values of the counters for a CPU.
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Limits {
+    dropped: Option<u32>,
+    time_squeeze: Option<u32>,
+}
+
+/// A counter that exceeded its threshold.
+#[derive(Debug, Eq, PartialEq)]
+struct Violation {
+    cpu: u32,
+    field: &'static str,
+    value: u32,
+    limit: u32,
+}
+
+fn check_thresholds(rows: &[Row], thresholds: &Thresholds) -> Vec<Violation> {
+    let mut violations = vec![];
+
+    for row in rows {
+        let cpu = row.cpu();
+        let limits = thresholds.cpus.get(&cpu);
+        let limit = |field: fn(&Limits) -> Option<u32>| {
+            limits
+                .and_then(field)
+                .or_else(|| field(&thresholds.default))
+        };
+
+        let checks = [
+            ("dropped", row.stat.dropped, limit(|l| l.dropped)),
+            (
+                "time_squeeze",
+                row.stat.time_squeeze,
+                limit(|l| l.time_squeeze),
+            ),
+        ];
+
+        for (field, value, limit) in checks.iter() {
+            if let Some(limit) = limit {
+                if value > limit {
+                    violations.push(Violation {
+                        cpu,
+                        field,
+                        value: *value,
+                        limit: *limit,
+                    });
+                }
+            }
+        }
+    }
+
+    violations
+}
+
+/// Find the first line that does not report a `cpu_id`.
+fn missing_cpu_id(stats: &[SoftnetStat]) -> Option<usize> {
+    stats.iter().position(|stat| stat.cpu_id.is_none())
+}
+
+/// Whether any line reports a `cpu_id` other than its position in the file.
+///
+/// The kernel only writes lines for online CPUs, so once one is offline every later line is
+/// labelled with a higher cpu_id than its index.
+fn has_offline_cpus(stats: &[SoftnetStat]) -> bool {
+    stats.iter().enumerate().any(|(index, stat)| {
+        stat.cpu_id
+            .map_or(false, |cpu_id| cpu_id != line_cpu(index))
+    })
+}
+
+/// Sum rows that share a `cpu_id` into the first row with that id, returning the merged rows and
+/// how many rows were folded into another. As in `aggregate`, the backlog length is not summed, and
+/// the longest one is kept.
+///
+/// Rows from kernels that do not report `cpu_id` are never merged, since their CPU is only known
+/// from their position in the file.
+fn merge_duplicates(rows: Vec<Row>) -> (Vec<Row>, usize) {
+    let mut merged: Vec<Row> = Vec::with_capacity(rows.len());
+    let mut positions: HashMap<u32, usize> = HashMap::new();
+    let mut count = 0;
+
+    for row in rows {
+        let cpu_id = match row.stat.cpu_id {
+            Some(cpu_id) => cpu_id,
+            None => {
+                merged.push(row);
+                continue;
+            }
+        };
+
+        match positions.get(&cpu_id) {
+            Some(&position) => {
+                let into = &mut merged[position].stat;
+                let from = &row.stat;
+
+                into.processed = add_counters(into.processed, from.processed);
+                into.dropped = add_counters(into.dropped, from.dropped);
+                into.time_squeeze = add_counters(into.time_squeeze, from.time_squeeze);
+                into.throttled = add_counters(into.throttled, from.throttled);
+                into.fastroute_hit = add_counters(into.fastroute_hit, from.fastroute_hit);
+                into.fastroute_success =
+                    add_counters(into.fastroute_success, from.fastroute_success);
+                into.fastroute_defer = add_counters(into.fastroute_defer, from.fastroute_defer);
+                into.fastroute_deferred_out =
+                    add_counters(into.fastroute_deferred_out, from.fastroute_deferred_out);
+                into.cpu_collision = add_counters(into.cpu_collision, from.cpu_collision);
+                into.received_rps = add_optional_counters(into.received_rps, from.received_rps);
+                into.flow_limit_count =
+                    add_optional_counters(into.flow_limit_count, from.flow_limit_count);
+                into.backlog_len = into.backlog_len.max(from.backlog_len);
+
+                count += 1;
+            }
+            None => {
+                positions.insert(cpu_id, merged.len());
+                merged.push(row);
+            }
+        }
+    }
+
+    (merged, count)
+}
+
+/// A column that rows can be sorted by.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum SortField {
+    Cpu,
+    Processed,
+    Dropped,
+    TimeSqueeze,
+    CpuCollision,
+    ReceivedRps,
+    FlowLimitCount,
+    BacklogLen,
+}
+
+impl SortField {
+    fn value(self, row: &Row) -> Option<u32> {
+        let stat = &row.stat;
+
+        match self {
+            SortField::Cpu => Some(row.cpu()),
+            SortField::Processed => Some(stat.processed),
+            SortField::Dropped => Some(stat.dropped),
+            SortField::TimeSqueeze => Some(stat.time_squeeze),
+            SortField::CpuCollision => Some(stat.cpu_collision),
+            SortField::ReceivedRps => stat.received_rps,
+            SortField::FlowLimitCount => stat.flow_limit_count,
+            SortField::BacklogLen => stat.backlog_len,
+        }
+    }
+}
+
+/// A single sort key, such as `dropped` or `processed:asc`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct SortKey {
+    field: SortField,
+    descending: bool,
+}
+
+/// Parse a comma-separated list of cpus and inclusive ranges of cpus, such as `0-3,8`.
+fn parse_cpus(spec: &str) -> Result<Vec<RangeInclusive<u32>>, String> {
+    let cpu = |value: &str| {
+        value
+            .trim()
+            .parse::<u32>()
+            .map_err(|_| format!("`{}` is not a cpu", value))
+    };
+
+    spec.split(',')
+        .map(|item| match item.split_once('-') {
+            Some((start, end)) => {
+                let (start, end) = (cpu(start)?, cpu(end)?);
+                if start > end {
+                    return Err(format!("`{}` is an empty range", item));
+                }
+                Ok(start..=end)
+            }
+            None => cpu(item).map(|cpu| cpu..=cpu),
+        })
+        .collect()
+}
+
+/// The indexes of the stats for the cpus in `cpus`, from `parse_cpus`.
+///
+/// Like `Row::cpu`, a stat is matched by its cpu_id when the kernel reports one and by its
+/// position otherwise. Cpus that are not in `stats` are ignored.
+fn select_cpus(stats: &[SoftnetStat], cpus: &[RangeInclusive<u32>]) -> Vec<usize> {
+    stats
+        .iter()
+        .enumerate()
+        .filter(|(index, stat)| {
+            let cpu = stat.cpu_id.unwrap_or_else(|| line_cpu(*index));
+            cpus.iter().any(|range| range.contains(&cpu))
+        })
+        .map(|(index, _)| index)
+        .collect()
+}
+
+/// Parse a comma-separated list of sort keys.
+///
+/// Each key is a field name optionally followed by `:asc` or `:desc`. Counters sort descending
+/// by default so the busiest CPUs come first, while `cpu` sorts ascending.
+fn parse_sort_keys(spec: &str) -> Result<Vec<SortKey>, String> {
+    spec.split(',')
+        .map(|key| {
+            let (name, direction) = match key.split_once(':') {
+                Some((name, direction)) => (name, Some(direction)),
+                None => (key, None),
+            };
+
+            let field = match name {
+                "cpu" => SortField::Cpu,
+                "processed" => SortField::Processed,
+                "dropped" => SortField::Dropped,
+                "time_squeeze" => SortField::TimeSqueeze,
+                "cpu_collision" => SortField::CpuCollision,
+                "received_rps" => SortField::ReceivedRps,
+                "flow_limit_count" => SortField::FlowLimitCount,
+                "backlog_len" => SortField::BacklogLen,
+                _ => return Err(format!("unknown sort field `{}`", name)),
+            };
+
+            let descending = match direction {
+                None => field != SortField::Cpu,
+                Some("asc") => false,
+                Some("desc") => true,
+                Some(direction) => {
+                    return Err(format!(
+                        "unknown sort direction `{}`, expected `asc` or `desc`",
+                        direction
+                    ))
+                }
+            };
+
+            Ok(SortKey { field, descending })
+        })
+        .collect()
+}
+
+/// Sort rows by each key in turn, always breaking remaining ties by ascending cpu so the output is
+/// deterministic.
+fn sort_rows(rows: &mut [Row], keys: &[SortKey]) {
+    rows.sort_by(|a, b| {
+        keys.iter()
+            .map(|key| {
+                let ordering = key.field.value(a).cmp(&key.field.value(b));
+                if key.descending {
+                    ordering.reverse()
+                } else {
+                    ordering
+                }
+            })
+            .fold(Ordering::Equal, Ordering::then)
+            .then(a.cpu().cmp(&b.cpu()))
+            .then(a.index.cmp(&b.index))
+    });
+}
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("softnet-stat: {}", e);
+        process::exit(1);
+    }
+}
+
+fn run() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = env::args().collect();
+    let program = args[0].clone();
+
+    let mut opts = Options::new();
+    opts.optflag("j", "json", "use json output");
+    opts.optflag(
+        "",
+        "with-metadata",
+        "wrap json output with a description of each field and the kernels that support it",
+    );
+    opts.optflag("", "pretty", "indent json output");
+    opts.optflag(
+        "",
+        "ndjson",
+        "use json output with one object per cpu on each line",
+    );
+    opts.optflag(
+        "",
+        "timestamp",
+        "wrap the json output in an object with the time the stats were read",
+    );
+    opts.optflag(
+        "",
+        "yaml",
+        "use yaml output, leaving out the fields the kernel does not report",
+    );
+    opts.optflag("c", "csv", "use csv output");
+    opts.optflag("p", "prometheus", "use prometheus output");
+    opts.optflag("", "influx", "use influxdb line protocol output");
+    opts.optflag(
+        "",
+        "statsd",
+        "use statsd output, to pipe into a statsd agent",
+    );
+    opts.optflag(
+        "",
+        "statusline",
+        "print a one-line summary of the whole machine for status bars",
+    );
+    opts.optflag(
+        "",
+        "human",
+        "shorten large counters in the table with K, M and G suffixes",
+    );
+    opts.optflag("", "total", "end the table with a row that sums every cpu");
+    opts.optopt(
+        "",
+        "width",
+        "make every column of the table N characters wide, instead of fitting them to their contents",
+        "N",
+    );
+    opts.optflag(
+        "",
+        "no-header",
+        "leave out the line above the table that names the kernel versions the fields match",
+    );
+    opts.optopt(
+        "",
+        "color",
+        "highlight table rows with drops or time squeezes: auto, always or never (default: auto)",
+        "WHEN",
+    );
+    opts.optflag("h", "help", "print this help menu");
+    opts.optflag("s", "stdin", "read from stdin");
+    opts.optopt(
+        "f",
+        "file",
+        "read from PATH instead of /proc/net/softnet_stat",
+        "PATH",
+    );
+    opts.optopt(
+        "w",
+        "watch",
+        "re-read and print the stats every SECS seconds until interrupted",
+        "SECS",
+    );
+    opts.optflag(
+        "",
+        "rate",
+        "print the per second rate of each counter between two samples, one --watch interval or a second apart",
+    );
+    opts.optflag(
+        "",
+        "diff",
+        "print the change in each counter between two snapshot files, given as the OLD NEW arguments",
+    );
+    opts.optopt(
+        "",
+        "input-delimiter",
+        "the character separating fields in the input (default: space)",
+        "CHAR",
+    );
+    opts.optopt(
+        "",
+        "bench",
+        "time N parses of the input, and N reads of the file, and print their rates instead of the stats",
+        "N",
+    );
+    opts.optflag(
+        "",
+        "dump-layout",
+        "print how each field of the first line maps to a stat, instead of the stats",
+    );
+    opts.optflag(
+        "",
+        "strict",
+        "fail if the lines do not all have the same number of fields",
+    );
+    opts.optflag(
+        "",
+        "frames-per-squeeze",
+        "add a softnet_frames_per_squeeze gauge to prometheus output",
+    );
+    opts.optopt(
+        "",
+        "cpu-label-name",
+        "name of the cpu label in prometheus output (default: cpu)",
+        "NAME",
+    );
+    opts.optopt(
+        "",
+        "cpu-label-format",
+        "value of the cpu label in prometheus output, where {id} is replaced by the cpu id (default: cpu{id})",
+        "TEMPLATE",
+    );
+    opts.optopt(
+        "",
+        "metric-prefix",
+        "prefix of the metric names in prometheus and statsd output, and the measurement in influx output (default: softnet)",
+        "PREFIX",
+    );
+    opts.optflag(
+        "",
+        "require-cpu-id",
+        "fail if any line does not report its cpu_id",
+    );
+    opts.optflag(
+        "",
+        "merge-duplicates",
+        "sum rows that share a cpu_id into a single row",
+    );
+    opts.optopt(
+        "",
+        "cpu",
+        "only show the cpus in a comma-separated list of cpus and ranges, such as 0-3,8",
+        "CPUS",
+    );
+    opts.optopt(
+        "",
+        "sort",
+        "sort rows by a comma-separated list of fields, each optionally suffixed with :asc or :desc",
+        "KEYS",
+    );
+    opts.optopt(
+        "",
+        "thresholds",
+        "exit with status 2 if any cpu exceeds the limits in a json FILE of per-cpu thresholds",
+        "FILE",
+    );
+    opts.optflagopt(
+        "",
+        "fail-on-drops",
+        "exit with status 2 if any cpu has dropped more than N frames (default: 0)",
+        "N",
+    );
+    opts.optflagopt(
+        "",
+        "fail-on-squeeze",
+        "exit with status 2 if any cpu has been squeezed more than N times (default: 0)",
+        "N",
+    );
+    opts.optopt(
+        "",
+        "serve",
+        "serve prometheus metrics over http on ADDR, such as 0.0.0.0:9110, instead of printing them",
+        "ADDR",
+    );
+    opts.optopt(
+        "",
+        "split-by-cpu",
+        "write one file per cpu into DIR instead of stdout",
+        "DIR",
+    );
+    opts.optopt(
+        "o",
+        "output",
+        "write to PATH instead of stdout, replacing it in one step so readers never see part of it",
+        "PATH",
+    );
+
+    let matches = opts.parse(&args[1..])?;
+
+    if matches.opt_present("h") {
+        print_usage(&program, opts);
+        return Ok(());
+    }
+
+    // Only --diff takes arguments, the OLD and NEW files.
+    if !matches.opt_present("diff") {
+        if let Some(arg) = matches.free.first() {
+            return Err(format!("unexpected argument `{}`", arg).into());
+        }
+    }
+
+    if matches.opt_present("f") && matches.opt_present("s") {
+        return Err("--file and --stdin cannot be used together".into());
+    }
+    let file = matches
+        .opt_str("f")
+        .unwrap_or_else(|| "/proc/net/softnet_stat".to_string());
+    let file = file.as_str();
+
+    if matches.opt_present("output") && matches.opt_present("split-by-cpu") {
+        return Err("--output cannot be combined with --split-by-cpu".into());
+    }
+
+    let delimiter = match matches.opt_str("input-delimiter") {
+        Some(delimiter) => Some(
+            parse_delimiter(&delimiter).map_err(|e| format!("invalid --input-delimiter: {}", e))?,
+        ),
+        None => None,
+    };
+    let sort_keys = match matches.opt_str("sort") {
+        Some(spec) => Some(parse_sort_keys(&spec).map_err(|e| format!("invalid --sort: {}", e))?),
+        None => None,
+    };
+    let cpus = match matches.opt_str("cpu") {
+        Some(spec) => Some(parse_cpus(&spec).map_err(|e| format!("invalid --cpu: {}", e))?),
+        None => None,
+    };
+    let pipeline = Pipeline {
+        delimiter,
+        strict: matches.opt_present("strict"),
+        require_cpu_id: matches.opt_present("require-cpu-id"),
+        cpus,
+        merge_duplicates: matches.opt_present("merge-duplicates"),
+        sort_keys,
+        note_offline_cpus: AtomicBool::new(false),
+    };
+
+    let watch = match matches.opt_str("w") {
+        Some(interval) => {
+            Some(parse_interval(&interval).map_err(|e| format!("invalid --watch: {}", e))?)
+        }
+        None => None,
+    };
+    if watch.is_some() && (matches.opt_present("bench") || matches.opt_present("dump-layout")) {
+        return Err("--watch cannot be combined with --bench or --dump-layout".into());
+    }
+
+    let rate = matches.opt_present("rate");
+    if rate {
+        if matches.opt_present("bench") || matches.opt_present("dump-layout") {
+            return Err("--rate cannot be combined with --bench or --dump-layout".into());
+        }
+        if matches.opt_present("ndjson")
+            || matches.opt_present("c")
+            || matches.opt_present("p")
+            || matches.opt_present("statusline")
+            || matches.opt_present("influx")
+            || matches.opt_present("statsd")
+            || matches.opt_present("yaml")
+            || matches.opt_present("with-metadata")
+            || matches.opt_present("split-by-cpu")
+            || matches.opt_present("output")
+        {
+            return Err("--rate can only print a table or --json to stdout".into());
+        }
+    }
+    let interval = watch.unwrap_or(RATE_INTERVAL);
+
+    let format = if matches.opt_present("ndjson") {
+        Format::Ndjson
+    } else if matches.opt_present("j") {
+        Format::Json
+    } else if matches.opt_present("c") {
+        Format::Csv
+    } else if matches.opt_present("p") {
+        Format::Prometheus
+    } else if matches.opt_present("statusline") {
+        Format::Statusline
+    } else if matches.opt_present("influx") {
+        Format::Influx
+    } else if matches.opt_present("statsd") {
+        Format::Statsd
+    } else if matches.opt_present("yaml") {
+        if !cfg!(feature = "yaml") {
+            return Err("--yaml needs softnet-stat to be built with the yaml feature".into());
+        }
+        Format::Yaml
+    } else {
+        Format::Table
+    };
+
+    // The table numbers its rows by line, which is only the CPU while every CPU is online.
+    pipeline
+        .note_offline_cpus
+        .store(matches!(format, Format::Table), AtomicOrdering::Relaxed);
+
+    let mut options = OutputOptions::default();
+    if matches.opt_present("with-metadata") {
+        if !matches!(format, Format::Json | Format::Yaml) {
+            return Err("--with-metadata can only be used with --json or --yaml".into());
+        }
+        options.with_metadata = true;
+    }
+    if matches.opt_present("timestamp") {
+        if !matches!(format, Format::Json | Format::Yaml) || rate {
+            return Err("--timestamp can only be used with --json or --yaml".into());
+        }
+        options.timestamp = true;
+    }
+    if matches.opt_present("pretty") {
+        if matches.opt_present("ndjson") {
+            return Err("--pretty and --ndjson cannot be used together".into());
+        }
+        if !matches!(format, Format::Json) {
+            return Err("--pretty can only be used with --json".into());
+        }
+        options.pretty = true;
+    }
+    let header = !matches.opt_present("no-header");
+    if let Some(width) = matches.opt_str("width") {
+        options.width = match width.parse() {
+            Ok(width) if width > 0 => Some(width),
+            _ => return Err(format!("invalid --width: `{}` is not a width", width).into()),
+        };
+    }
+    options.frames_per_squeeze = matches.opt_present("frames-per-squeeze");
+    options.human = matches.opt_present("human");
+    if matches.opt_present("total") {
+        if !matches!(format, Format::Table) || rate {
+            return Err("--total can only be used with the table output".into());
+        }
+        options.total = true;
+    }
+    // Only the tables are colored, and only when they are written to stdout.
+    let when = matches
+        .opt_str("color")
+        .unwrap_or_else(|| "auto".to_string());
+    options.color = color::enabled(&when).map_err(|e| format!("invalid --color: {}", e))?
+        && !matches.opt_present("split-by-cpu")
+        && !matches.opt_present("output");
+    if matches.opt_present("cpu-label-name") || matches.opt_present("cpu-label-format") {
+        let name = matches
+            .opt_str("cpu-label-name")
+            .unwrap_or(options.cpu_label.name);
+        let template = matches
+            .opt_str("cpu-label-format")
+            .unwrap_or(options.cpu_label.template);
+        options.cpu_label =
+            CpuLabel::new(&name, &template).map_err(|e| format!("invalid cpu label: {}", e))?;
+    }
+
+    if let Some(prefix) = matches.opt_str("metric-prefix") {
+        options.metric_prefix =
+            MetricPrefix::new(&prefix).map_err(|e| format!("invalid --metric-prefix: {}", e))?;
+    }
+
+    let mut thresholds = match matches.opt_str("thresholds") {
+        Some(path) => Some(read_thresholds(&path)?),
+        None => None,
+    };
+    // The limits on the command line apply to every cpu without a limit of its own in the file.
+    let fail_on = |option: &str| match matches.opt_str(option) {
+        Some(value) => value
+            .parse()
+            .map(Some)
+            .map_err(|_| format!("invalid --{}: `{}` is not a count", option, value)),
+        None => Ok(matches.opt_present(option).then(|| 0)),
+    };
+    if let Some(limit) = fail_on("fail-on-drops")? {
+        thresholds
+            .get_or_insert_with(Thresholds::default)
+            .default
+            .dropped = Some(limit);
+    }
+    if let Some(limit) = fail_on("fail-on-squeeze")? {
+        thresholds
+            .get_or_insert_with(Thresholds::default)
+            .default
+            .time_squeeze = Some(limit);
+    }
+
+    if matches.opt_present("diff") {
+        let (old, new) = match &matches.free[..] {
+            [old, new] => (old, new),
+            _ => return Err("--diff needs the OLD and NEW files to compare".into()),
+        };
+        for option in [
+            "stdin",
+            "file",
+            "watch",
+            "rate",
+            "bench",
+            "dump-layout",
+            "split-by-cpu",
+            "output",
+            "thresholds",
+            "fail-on-drops",
+            "fail-on-squeeze",
+            "json",
+            "ndjson",
+            "csv",
+            "prometheus",
+            "statusline",
+            "influx",
+            "statsd",
+            "yaml",
+            "total",
+            "cpu",
+            "sort",
+            "merge-duplicates",
+            "strict",
+            "require-cpu-id",
+        ] {
+            if matches.opt_present(option) {
+                return Err(format!("--diff cannot be combined with --{}", option).into());
+            }
+        }
+
+        let snapshot = |path: &str| -> Result<Vec<SoftnetStat>, Box<dyn Error>> {
+            let mut raw = read_input(path)?;
+            pipeline.replace_delimiter(&mut raw);
+            let stats = softnet_stat::parse(&raw).map_err(|e| format!("{}: {}", path, e))?;
+
+            Ok(stats)
+        };
+        let deltas = diff(&snapshot(old)?, &snapshot(new)?);
+
+        let stdout = io::stdout();
+        print_diff(stdout.lock(), &deltas, &options)?;
+
+        return Ok(());
+    }
+
+    if let Some(addr) = matches.opt_str("serve") {
+        for option in [
+            "stdin",
+            "watch",
+            "rate",
+            "bench",
+            "dump-layout",
+            "split-by-cpu",
+            "output",
+            "thresholds",
+            "fail-on-drops",
+            "fail-on-squeeze",
+            "json",
+            "ndjson",
+            "csv",
+            "statusline",
+            "influx",
+            "statsd",
+            "yaml",
+            "total",
+            "diff",
+        ] {
+            if matches.opt_present(option) {
+                return Err(format!("--serve cannot be combined with --{}", option).into());
+            }
+        }
+
+        let listener =
+            TcpListener::bind(&addr).map_err(|e| format!("failed to listen on {}: {}", addr, e))?;
+        eprintln!("listening on {}", listener.local_addr()?);
+
+        let file = file.to_string();
+        serve::serve(listener, move || {
+            let mut raw = read_input(&file).map_err(|e| e.to_string())?;
+            pipeline.replace_delimiter(&mut raw);
+            let rows = pipeline.rows(&file, &raw).map_err(|e| e.to_string())?;
+
+            let mut body = vec![];
+            prometheus(&mut body, &rows, &options).map_err(|e| e.to_string())?;
+            Ok(body)
+        })?;
+        return Ok(());
+    }
+
+    if watch.is_some() {
+        interrupt::install().map_err(|e| format!("failed to install SIGINT handler: {}", e))?;
+    }
+    // Keys come from the terminal, so they control a watch of a file but not one of stdin, which
+    // refreshes as each snapshot arrives.
+    let mut terminal = if watch.is_some() && !matches.opt_present("s") {
+        keys::Terminal::open()
+    } else {
+        None
+    };
+    let mut paused = false;
+
+    let mut samples = 0;
+    let mut previous: Option<(Instant, Vec<Row>)> = None;
+    loop {
+        let mut raw = if matches.opt_present("s") {
+            let stdin = io::stdin();
+            if watch.is_some() || rate {
+                // Each snapshot is separated from the next by a blank line, and end of input ends
+                // the watch.
+                match read_snapshot(&mut stdin.lock()) {
+                    Ok(Some(raw)) => raw,
+                    Ok(None) => break,
+                    // Ctrl-C while waiting for the next snapshot.
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => break,
+                    Err(e) => return Err(format!("failed to read stdin: {}", e).into()),
+                }
+            } else {
+                read_proc_file(stdin).map_err(|e| format!("failed to read stdin: {}", e))?
+            }
+        } else {
+            read_input(file)?
+        };
+        pipeline.replace_delimiter(&mut raw);
+
+        if matches.opt_present("dump-layout") {
+            let line = raw.split(|b| *b == b'\n').next().unwrap_or_default();
+            let stdout = io::stdout();
+            dump_layout(stdout.lock(), line)?;
+            return Ok(());
+        }
+
+        let source = if matches.opt_present("s") {
+            "stdin"
+        } else {
+            file
+        };
+        let rows = pipeline.rows(source, &raw)?;
+
+        if let Some(iterations) = matches.opt_str("bench") {
+            let iterations: u32 = iterations.parse().ok().filter(|n| *n > 0).ok_or_else(|| {
+                format!("invalid --bench: `{}` is not a positive count", iterations)
+            })?;
+            let (elapsed, lines) = bench(&raw, iterations);
+            print!(
+                "{} parses of {} lines in {:.3}s ({} parses/sec)",
+                iterations,
+                lines,
+                elapsed.as_secs_f64(),
+                bench_rate(iterations, elapsed)
+            );
+            // Stdin can only be read once, so there is no read to compare the parses with.
+            if !matches.opt_present("s") {
+                let (elapsed, bytes) = bench_read(file, iterations)
+                    .map_err(|e| format!("failed to read {}: {}", file, e))?;
+                print!(
+                    "; {} reads of {} bytes in {:.3}s ({} reads/sec)",
+                    iterations,
+                    bytes,
+                    elapsed.as_secs_f64(),
+                    bench_rate(iterations, elapsed)
+                );
+            }
+            println!();
+            return Ok(());
+        }
+
+        if rate {
+            if let Some((then, prev)) = &previous {
+                // Snapshots on stdin were taken one interval apart, however fast they are read.
+                let secs = if matches.opt_present("s") {
+                    interval.as_secs_f64()
+                } else {
+                    then.elapsed().as_secs_f64()
+                };
+                let stdout = io::stdout();
+                let mut out = stdout.lock();
+                if samples > 1 && matches!(format, Format::Table) {
+                    writeln!(out)?;
+                }
+                let rates = rates(prev, &rows, secs);
+                if matches!(format, Format::Json) {
+                    json_rates(&mut out, &rates, options.pretty)?;
+                } else {
+                    print_rates(&mut out, &rates, &options)?;
+                }
+            }
+        } else if let Some(dir) = matches.opt_str("split-by-cpu") {
+            split_by_cpu(Path::new(&dir), format, &rows, &options)
+                .map_err(|e| format!("failed to write per-cpu files to {}: {}", dir, e))?;
+        } else if let Some(path) = matches.opt_str("output") {
+            // Each sample of --watch replaces the last, rather than being appended.
+            write_atomic(Path::new(&path), |file| {
+                if matches!(format, Format::Table) && header {
+                    kernel_header(&mut *file, &rows)?;
+                }
+                format.write(file, &rows, &options)
+            })
+            .map_err(|e| format!("failed to write {}: {}", path, e))?;
+        } else {
+            let stdout = io::stdout();
+            let mut out = stdout.lock();
+            if watch.is_some() && samples > 0 && matches!(format, Format::Table) {
+                writeln!(out)?;
+            }
+            if matches!(format, Format::Table) && header {
+                kernel_header(&mut out, &rows)?;
+            }
+            format.write(&mut out, &rows, &options)?;
+            if watch.is_some() && matches!(format, Format::Statusline) {
+                writeln!(out)?;
+            }
+        }
+
+        if let Some(thresholds) = &thresholds {
+            let violations = check_thresholds(&rows, thresholds);
+            for violation in violations.iter() {
+                eprintln!(
+                    "cpu{}: {} is {}, above the threshold of {}",
+                    violation.cpu, violation.field, violation.value, violation.limit
+                );
+            }
+
+            if !violations.is_empty() {
+                // process::exit skips destructors, so put the terminal back first.
+                drop(terminal.take());
+                process::exit(2);
+            }
+        }
+
+        samples += 1;
+        if rate {
+            previous = Some((Instant::now(), rows));
+        }
+
+        if watch.is_some() {
+            if keys::wait(interval, terminal.as_mut(), &mut paused) == keys::Wake::Quit {
+                break;
+            }
+        } else if rate && samples == 1 {
+            if !matches.opt_present("s") {
+                thread::sleep(interval);
+            }
+        } else {
+            break;
+        }
+    }
+
+    if rate && samples < 2 {
+        return Err("--rate needs at least two samples of the input".into());
+    }
+
+    Ok(())
+}
+
+/// Write the line above the table naming the kernels that could have written `rows`.
+fn kernel_header<W>(mut out: W, rows: &[Row]) -> io::Result<()>
+where
+    W: io::Write,
+{
+    if rows.is_empty() {
+        return Ok(());
+    }
+
+    let stats: Vec<SoftnetStat> = rows.iter().map(|row| row.stat.clone()).collect();
+    writeln!(
+        out,
+        "Kernel {} (inferred from the fields present)",
+        infer_kernel_era(&stats)
+    )
+}
+
+/// How the rows of a sample are built from the raw input.
+struct Pipeline {
+    delimiter: Option<u8>,
+    strict: bool,
+    require_cpu_id: bool,
+    cpus: Option<Vec<RangeInclusive<u32>>>,
+    merge_duplicates: bool,
+    sort_keys: Option<Vec<SortKey>>,
+    /// Whether to note, once, that the rows of the table are not numbered by CPU because some are
+    /// offline.
+    note_offline_cpus: AtomicBool,
+}
+
+impl Pipeline {
+    fn replace_delimiter(&self, raw: &mut [u8]) {
+        if let Some(delimiter) = self.delimiter {
+            replace_delimiter(raw, delimiter);
+        }
+    }
+
+    /// Parse and check the input, then select, merge and sort the rows.
+    fn rows(&self, source: &str, raw: &[u8]) -> Result<Vec<Row>, Box<dyn Error>> {
+        let stats = softnet_stat::parse(raw).map_err(|e| format!("{}: {}", source, e))?;
+
+        if self.strict {
+            if let Some(i) = inconsistent_line(&stats) {
+                return Err(format!(
+                    "line {} of {} has {} fields, but line 1 has {}",
+                    i + 1,
+                    source,
+                    field_count(&stats[i]),
+                    field_count(&stats[0])
+                )
+                .into());
+            }
+        }
+
+        if has_offline_cpus(&stats) && self.note_offline_cpus.swap(false, AtomicOrdering::Relaxed) {
+            eprintln!(
+                "note: some CPUs in {} are offline, so the Cpu column numbers lines rather \
+                 than CPUs; see CPU Id",
+                source
+            );
+        }
+
+        if self.require_cpu_id {
+            if let Some(i) = missing_cpu_id(&stats) {
+                return Err(format!(
+                    "line {} of {} has no cpu_id; --require-cpu-id needs a v5.10 or later kernel",
+                    i + 1,
+                    source
+                )
+                .into());
+            }
+        }
+
+        let selected = self.cpus.as_ref().map(|cpus| select_cpus(&stats, cpus));
+
+        let mut rows: Vec<Row> = into_rows(stats)
+            .into_iter()
+            .filter(|row| {
+                selected
+                    .as_ref()
+                    .map_or(true, |selected| selected.contains(&row.index))
+            })
+            .collect();
+
+        if self.merge_duplicates {
+            let (merged, count) = merge_duplicates(rows);
+            if count > 0 {
+                eprintln!(
+                    "warning: merged {} rows that repeat a cpu_id; the input is not a single sample",
+                    count
+                );
+            }
+            rows = merged;
+        }
+
+        if let Some(keys) = &self.sort_keys {
+            sort_rows(&mut rows, keys);
+        }
+
+        Ok(rows)
+    }
+}
+
+fn read_input(file: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    let raw = File::open(file)
+        .and_then(read_proc_file)
+        .map_err(|e| format!("failed to read {}: {}", file, e))?;
+
+    Ok(raw)
+}
+
+fn read_thresholds(path: &str) -> Result<Thresholds, Box<dyn Error>> {
+    let handle =
+        File::open(path).map_err(|e| format!("failed to open thresholds file {}: {}", path, e))?;
+    let thresholds = serde_json::from_reader(io::BufReader::new(handle))
+        .map_err(|e| format!("failed to parse thresholds file {}: {}", path, e))?;
+
+    Ok(thresholds)
+}
+
+/// The stat each position of a line is parsed into.
+const LAYOUT: &[&str] = &[
+    "processed",
+    "dropped",
+    "time_squeeze",
+    "throttled",
+    "fastroute_hit",
+    "fastroute_success",
+    "fastroute_defer",
+    "fastroute_deferred_out",
+    "cpu_collision",
+    "received_rps",
+    "flow_limit_count",
+    "backlog_len",
+    "cpu_id",
+];
+
+/// Print the position, field name, hex token and decimal value of each token in a line.
+///
+/// This works on the raw tokens rather than parsed stats so it can help diagnose a line that does
+/// not parse.
+fn dump_layout<W>(mut out: W, line: &[u8]) -> io::Result<()>
+where
+    W: io::Write,
+{
+    writeln!(out, "{:<10}{:<24}{:<10}Decimal", "Position", "Field", "Hex")?;
+
+    let tokens = line
+        .split(|b| b.is_ascii_whitespace())
+        .filter(|token| !token.is_empty());
+
+    for (i, token) in tokens.enumerate() {
+        let token = String::from_utf8_lossy(token);
+        let field = LAYOUT.get(i).copied().unwrap_or("(unknown)");
+        let decimal = u32::from_str_radix(&token, 16)
+            .map_or_else(|_| "(not hex)".to_string(), |value| value.to_string());
+
+        writeln!(out, "{:<10}{:<24}{:<10}{}", i, field, token, decimal)?;
+    }
+
+    Ok(())
+}
+
+/// Time how long it takes to parse `raw` the given number of times.
+///
+/// The number of lines parsed in total is returned along with the time and printed, so the parses
+/// cannot be optimized away.
+fn bench(raw: &[u8], iterations: u32) -> (Duration, usize) {
+    let start = Instant::now();
+
+    let mut lines = 0;
+    for _ in 0..iterations {
+        lines += parse_softnet_stats(raw).map_or(0, |(_, stats)| stats.len());
+    }
+
+    (start.elapsed(), lines)
+}
+
+/// Time `iterations` reads of `file`, to compare with the time `bench` spends parsing it.
+///
+/// The number of bytes read in total is returned along with the time, as `bench` does with lines.
+fn bench_read(file: &str, iterations: u32) -> io::Result<(Duration, usize)> {
+    let start = Instant::now();
+
+    let mut bytes = 0;
+    for _ in 0..iterations {
+        bytes += File::open(file).and_then(read_proc_file)?.len();
+    }
+
+    Ok((start.elapsed(), bytes))
+}
+
+/// Format the rate of `count` things done in `elapsed`, or `-` if it took no measurable time.
+fn bench_rate(count: u32, elapsed: Duration) -> String {
+    ratio(f64::from(count), elapsed.as_secs_f64())
+        .map_or_else(|| "-".to_string(), |r| format!("{:.0}", r))
+}
+
+/// How far apart the two samples of `--rate` are taken without `--watch`.
+const RATE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The shortest `--watch` interval, so a typo cannot make us re-read the stats in a busy loop.
+const MIN_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Parse a `--watch` interval in seconds, raising it to `MIN_INTERVAL` if it is shorter.
+fn parse_interval(secs: &str) -> Result<Duration, String> {
+    let interval = secs
+        .parse::<f64>()
+        .ok()
+        .filter(|secs| secs.is_finite() && *secs > 0.0)
+        .ok_or_else(|| format!("`{}` is not a positive number of seconds", secs))?;
+
+    Ok(Duration::from_secs_f64(interval).max(MIN_INTERVAL))
+}
+
+/// Read the next snapshot of a softnet_stat file from a stream of them separated by blank lines.
+///
+/// Returns `None` once the stream is exhausted, and fails with `Interrupted` if SIGINT is received
+/// while waiting for it.
+fn read_snapshot<R>(input: &mut R) -> io::Result<Option<Vec<u8>>>
+where
+    R: io::BufRead,
+{
+    let mut snapshot = vec![];
+
+    loop {
+        let start = snapshot.len();
+        if interrupt::read_until(input, b'\n', &mut snapshot)? == 0 {
+            break;
+        }
+
+        if snapshot[start..].iter().all(u8::is_ascii_whitespace) {
+            snapshot.truncate(start);
+            // Blank lines before a snapshot are skipped rather than ending an empty one.
+            if !snapshot.is_empty() {
+                break;
+            }
+        }
+    }
+
+    if snapshot.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(snapshot))
+    }
+}
+
+/// Parse the argument to `--input-delimiter`, which must be a single character that cannot be
+/// mistaken for part of a field or a line ending.
+fn parse_delimiter(delimiter: &str) -> Result<u8, String> {
+    match delimiter.as_bytes() {
+        [b] if b.is_ascii_hexdigit() => Err(format!("`{}` is a hex digit", delimiter)),
+        [b'\n'] | [b'\r'] => Err("line endings cannot separate fields".to_string()),
+        [b] if b.is_ascii() => Ok(*b),
+        _ => Err(format!(
+            "`{}` is not a single ascii character",
+            delimiter.escape_default()
+        )),
+    }
+}
+
+/// Rewrite input that separates fields with `delimiter` into the space separated format the
+/// kernel writes.
+fn replace_delimiter(raw: &mut [u8], delimiter: u8) {
+    for byte in raw.iter_mut().filter(|byte| **byte == delimiter) {
+        *byte = b' ';
+    }
+}
+
+fn print_usage(program: &str, opts: Options) {
+    let brief = format!("Usage: {} [options]", program);
+    print!("{}", opts.usage(&brief));
+}
+
+fn print<W>(out: W, rows: &[Row], options: &OutputOptions) -> io::Result<()>
+where
+    W: io::Write,
+{
+    let counter = |value: u32| {
+        if options.human {
+            human_count(value)
+        } else {
+            value.to_string()
+        }
+    };
+    let header = [
+        "Cpu",
+        "Processed",
+        "Dropped",
+        "Drop %",
+        "Time Squeezed",
+        "Throttled",
+        "FR Hit",
+        "FR Success",
+        "FR Defer",
+        "FR Defer Out",
+        "Cpu Collision",
+        "Received RPS",
+        "Flow Limit Cnt",
+        "Backlog Length",
+        "CPU Id",
+        "Frames/Squeeze",
+    ];
+
+    let line = |cpu: String, stat: &SoftnetStat, cpu_id: String| {
+        let cells = vec![
+            cpu,
+            counter(stat.processed),
+            counter(stat.dropped),
+            format!("{:.2}", drop_percent(stat)),
+            counter(stat.time_squeeze),
+            counter(stat.throttled),
+            counter(stat.fastroute_hit),
+            counter(stat.fastroute_success),
+            counter(stat.fastroute_defer),
+            counter(stat.fastroute_deferred_out),
+            counter(stat.cpu_collision),
+            counter(stat.received_rps.unwrap_or_default()),
+            counter(stat.flow_limit_count.unwrap_or_default()),
+            counter(stat.backlog_len.unwrap_or_default()),
+            cpu_id,
+            frames_per_squeeze(stat).map_or_else(|| "-".to_string(), |f| format!("{:.1}", f)),
+        ];
+
+        TableLine {
+            cells,
+            color: highlight(options.color, stat.dropped > 0, stat.time_squeeze > 0),
+        }
+    };
+
+    let mut lines: Vec<TableLine> = rows
+        .iter()
+        .map(|row| {
+            let cpu_id = row.stat.cpu_id.unwrap_or_default().to_string();
+            line(row.index.to_string(), &row.stat, cpu_id)
+        })
+        .collect();
+
+    if options.total {
+        let stats: Vec<SoftnetStat> = rows.iter().map(|row| row.stat.clone()).collect();
+        lines.push(TableLine {
+            cells: vec![],
+            color: None,
+        });
+        lines.push(line(
+            "Total".to_string(),
+            &aggregate(&stats),
+            "-".to_string(),
+        ));
+    }
+
+    write_table(out, &header, &lines, options.width)
+}
+
+/// Print the change in each counter of every CPU, with the same layout as `print`.
+///
+/// CPUs that are only in one of the snapshots, or whose counters went down, are called out in the
+/// last column instead of having their changes printed as if they were meaningful.
+fn print_diff<W>(out: W, deltas: &[SoftnetDelta], options: &OutputOptions) -> io::Result<()>
+where
+    W: io::Write,
+{
+    let optional = |change: Option<i64>| change.map_or_else(|| "-".to_string(), |c| c.to_string());
+    let header = [
+        "Cpu",
+        "Processed",
+        "Dropped",
+        "Time Squeezed",
+        "Cpu Collision",
+        "Received RPS",
+        "Flow Limit Cnt",
+        "Backlog Length",
+        "Note",
+    ];
+    let only_in = |cpu: u32, note: &str| {
+        let mut cells = vec![String::new(); header.len()];
+        cells[0] = cpu.to_string();
+        cells[header.len() - 1] = note.to_string();
+        TableLine { cells, color: None }
+    };
+
+    let lines: Vec<TableLine> = deltas
+        .iter()
+        .map(|delta| match delta {
+            SoftnetDelta::Changed { cpu, change } => TableLine {
+                cells: vec![
+                    cpu.to_string(),
+                    change.processed.to_string(),
+                    change.dropped.to_string(),
+                    change.time_squeeze.to_string(),
+                    change.cpu_collision.to_string(),
+                    optional(change.received_rps),
+                    optional(change.flow_limit_count),
+                    change.backlog_len.unwrap_or_default().to_string(),
+                    if change.reset() { "counter reset" } else { "" }.to_string(),
+                ],
+                color: highlight(options.color, change.dropped > 0, change.time_squeeze > 0),
+            },
+            SoftnetDelta::Removed { cpu } => only_in(*cpu, "only in OLD"),
+            SoftnetDelta::Added { cpu } => only_in(*cpu, "only in NEW"),
+        })
+        .collect();
+
+    write_table(out, &header, &lines, options.width)
+}
+
+/// The color of a table row, if `color` is set and the CPU has dropped or been squeezed.
+///
+/// Drops are the more serious of the two, so they win when a CPU has both.
+fn highlight(color: bool, dropped: bool, squeezed: bool) -> Option<&'static str> {
+    if !color {
+        None
+    } else if dropped {
+        Some(color::RED)
+    } else if squeezed {
+        Some(color::YELLOW)
+    } else {
+        None
+    }
+}
+
+/// Print rates with the same layout as `print`.
+fn print_rates<W>(out: W, rows: &[RateRow], options: &OutputOptions) -> io::Result<()>
+where
+    W: io::Write,
+{
+    let per_second =
+        |value: Option<f64>| value.map_or_else(|| "-".to_string(), |v| format!("{:.1}", v));
+    let header = [
+        "Cpu",
+        "Processed/s",
+        "Dropped/s",
+        "Squeezed/s",
+        "Collision/s",
+        "RPS/s",
+        "Flow Limit/s",
+        "Backlog Length",
+        "CPU Id",
+    ];
+
+    let lines: Vec<TableLine> = rows
+        .iter()
+        .map(|row| {
+            let rate = &row.rate;
+
+            let cells = vec![
+                row.index.to_string(),
+                per_second(Some(rate.processed)),
+                per_second(Some(rate.dropped)),
+                per_second(Some(rate.time_squeeze)),
+                per_second(Some(rate.cpu_collision)),
+                per_second(rate.received_rps),
+                per_second(rate.flow_limit_count),
+                rate.backlog_len.unwrap_or_default().to_string(),
+                rate.cpu_id.unwrap_or_default().to_string(),
+            ];
+
+            TableLine {
+                cells,
+                color: highlight(options.color, rate.dropped > 0.0, rate.time_squeeze > 0.0),
+            }
+        })
+        .collect();
+
+    write_table(out, &header, &lines, options.width)
+}
+
+/// The cells of one line of a table, and the color to print it in. A line with no cells is
+/// printed as a blank line.
+struct TableLine {
+    cells: Vec<String>,
+    color: Option<&'static str>,
+}
+
+/// The space between columns that are sized to fit their contents.
+const COLUMN_GAP: usize = 2;
+
+/// The width of each column of a table, so the header and every line line up.
+///
+/// Columns are as wide as their widest cell plus a gap, unless `width` gives a fixed width for
+/// every column.
+fn column_widths(header: &[&str], lines: &[TableLine], width: Option<usize>) -> Vec<usize> {
+    if let Some(width) = width {
+        return vec![width; header.len()];
+    }
+
+    let mut widths: Vec<usize> = header.iter().map(|cell| cell.chars().count()).collect();
+    for line in lines {
+        for (width, cell) in widths.iter_mut().zip(line.cells.iter()) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    widths.iter().map(|width| width + COLUMN_GAP).collect()
+}
+
+/// Write the header and lines of a table with their columns aligned.
+///
+/// The last column is not padded, so lines have no trailing whitespace.
+fn write_table<W>(
+    mut out: W,
+    header: &[&str],
+    lines: &[TableLine],
+    width: Option<usize>,
+) -> io::Result<()>
+where
+    W: io::Write,
+{
+    let widths = column_widths(header, lines, width);
+    let write_cells = |out: &mut W, cells: &[&str]| -> io::Result<()> {
+        for (i, (cell, width)) in cells.iter().zip(widths.iter()).enumerate() {
+            if i + 1 == cells.len() {
+                write!(out, "{}", cell)?;
+            } else {
+                write!(out, "{:<width$}", cell, width = width)?;
+            }
+        }
+        Ok(())
+    };
+
+    write_cells(&mut out, header)?;
+    writeln!(out)?;
+
+    for line in lines {
+        let cells: Vec<&str> = line.cells.iter().map(String::as_str).collect();
+        write!(out, "{}", line.color.unwrap_or_default())?;
+        write_cells(&mut out, &cells)?;
+        if line.color.is_some() {
+            write!(out, "{}", color::RESET)?;
+        }
+        writeln!(out)?;
+    }
+
+    Ok(())
+}
+
+/// Shorten a counter with an SI suffix, such as `1.8G` for 1842008611.
+fn human_count(value: u32) -> String {
+    const SUFFIXES: [&str; 3] = ["K", "M", "G"];
+
+    if value < 1000 {
+        return value.to_string();
+    }
+
+    let mut scaled = f64::from(value) / 1000.0;
+    let mut suffix = 0;
+    // Move up a unit when rounding to one decimal would otherwise print `1000.0K`.
+    while scaled >= 999.95 && suffix < SUFFIXES.len() - 1 {
+        scaled /= 1000.0;
+        suffix += 1;
+    }
+
+    format!("{:.1}{}", scaled, SUFFIXES[suffix])
+}
+
+fn json<W>(
+    out: W,
+    rows: &[Row],
+    with_metadata: bool,
+    pretty: bool,
+    timestamp: Option<&str>,
+) -> io::Result<()>
+where
+    W: io::Write,
+{
+    write_json(out, &document(rows, with_metadata, timestamp), pretty)
+}
+
+/// Write the same document as `json` in YAML, leaving out the fields the kernel does not report.
+#[cfg(feature = "yaml")]
+fn yaml<W>(out: W, rows: &[Row], with_metadata: bool, timestamp: Option<&str>) -> io::Result<()>
+where
+    W: io::Write,
+{
+    yaml::to_writer(out, &document(rows, with_metadata, timestamp))
+}
+
+fn document<'a>(rows: &'a [Row], with_metadata: bool, timestamp: Option<&'a str>) -> Document<'a> {
+    let stats: Vec<JsonRow> = rows
+        .iter()
+        .map(|row| JsonRow {
+            cpu: None,
+            stat: &row.stat,
+        })
+        .collect();
+
+    if let Some(timestamp) = timestamp {
+        Document::Timestamped(SoftnetReport {
+            timestamp,
+            meta: with_metadata.then(|| Meta { fields: FIELDS }),
+            stats,
+        })
+    } else if with_metadata {
+        Document::Metadata(MetadataReport {
+            meta: Meta { fields: FIELDS },
+            stats,
+        })
+    } else {
+        Document::Stats(stats)
+    }
+}
+
+/// Format `time` as an RFC 3339 timestamp in UTC, to the second.
+fn rfc3339(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let (days, secs) = (secs / 86_400, secs % 86_400);
+
+    // The proleptic Gregorian date of a day since the epoch, from Howard Hinnant's
+    // civil_from_days. Eras are the 400 year cycles the calendar repeats in, counted from 0000-03-01.
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = era * 400 + year_of_era + u64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        secs / 3_600,
+        secs % 3_600 / 60,
+        secs % 60
+    )
+}
+
+/// Write each row as a json object on its own line, tagged with its cpu.
+fn ndjson<W>(mut out: W, rows: &[Row]) -> io::Result<()>
+where
+    W: io::Write,
+{
+    for row in rows {
+        let stat = JsonRow {
+            cpu: Some(row.cpu()),
+            stat: &row.stat,
+        };
+        write_json(&mut out, &stat, false)?;
+    }
+
+    Ok(())
+}
+
+fn write_json<W, T>(mut out: W, value: &T, pretty: bool) -> io::Result<()>
+where
+    W: io::Write,
+    T: serde::Serialize,
+{
+    if pretty {
+        serde_json::to_writer_pretty(&mut out, value)?;
+    } else {
+        serde_json::to_writer(&mut out, value)?;
+    }
+
+    writeln!(out)
+}
+
+fn json_rates<W>(out: W, rows: &[RateRow], pretty: bool) -> io::Result<()>
+where
+    W: io::Write,
+{
+    let rates: Vec<JsonRow<SoftnetRate>> = rows
+        .iter()
+        .map(|row| JsonRow {
+            cpu: None,
+            stat: &row.rate,
+        })
+        .collect();
+
+    write_json(out, &rates, pretty)
+}
+
+/// Write one line per cpu, leaving the fields the kernel does not report empty.
+fn csv<W>(mut out: W, rows: &[Row]) -> io::Result<()>
+where
+    W: io::Write,
+{
+    let optional = |value: Option<u32>| value.map(|v| v.to_string()).unwrap_or_default();
+
+    writeln!(
+        out,
+        "cpu,processed,dropped,time_squeeze,cpu_collision,received_rps,flow_limit_count,backlog_len,cpu_id"
+    )?;
+
+    for row in rows {
+        let stat = &row.stat;
+        writeln!(
+            out,
+            "{},{},{},{},{},{},{},{},{}",
+            row.cpu(),
+            stat.processed,
+            stat.dropped,
+            stat.time_squeeze,
+            stat.cpu_collision,
+            optional(stat.received_rps),
+            optional(stat.flow_limit_count),
+            optional(stat.backlog_len),
+            optional(stat.cpu_id)
+        )?;
+    }
+
+    Ok(())
+}
+
+/// The share of the frames that reached the CPU that were dropped, as a percentage.
+///
+/// A CPU that has seen no frames has dropped none of them, so `0.0` is returned rather than NaN.
+fn drop_percent(stat: &SoftnetStat) -> f64 {
+    let frames = f64::from(stat.processed) + f64::from(stat.dropped);
+
+    ratio(f64::from(stat.dropped), frames).map_or(0.0, |share| share * 100.0)
+}
+
+/// The average number of frames processed between each time the `net_rx_action` budget ran out.
+///
+/// A CPU that has never been squeezed has no ratio, so `None` is returned.
+fn frames_per_squeeze(stat: &SoftnetStat) -> Option<f64> {
+    ratio(f64::from(stat.processed), f64::from(stat.time_squeeze))
+}
+
+/// A metric written by `prometheus`, and the `SoftnetStat` field it reports.
+struct Metric {
+    /// The name of the metric after the prefix.
+    name: &'static str,
+    field: &'static str,
+    kind: &'static str,
+    /// The value of the metric, or `None` if the kernel does not report the field.
+    value: fn(&SoftnetStat) -> Option<u32>,
+}
+
+const METRICS: &[Metric] = &[
+    Metric {
+        name: "frames_processed",
+        field: "processed",
+        kind: "counter",
+        value: |stat| Some(stat.processed),
+    },
+    Metric {
+        name: "frames_dropped",
+        field: "dropped",
+        kind: "counter",
+        value: |stat| Some(stat.dropped),
+    },
+    Metric {
+        name: "time_squeeze",
+        field: "time_squeeze",
+        kind: "counter",
+        value: |stat| Some(stat.time_squeeze),
+    },
+    Metric {
+        name: "cpu_collisions",
+        field: "cpu_collision",
+        kind: "counter",
+        value: |stat| Some(stat.cpu_collision),
+    },
+    Metric {
+        name: "received_rps",
+        field: "received_rps",
+        kind: "counter",
+        value: |stat| stat.received_rps,
+    },
+    Metric {
+        name: "flow_limit_count",
+        field: "flow_limit_count",
+        kind: "counter",
+        value: |stat| stat.flow_limit_count,
+    },
+    Metric {
+        name: "backlog_len",
+        field: "backlog_len",
+        kind: "gauge",
+        value: |stat| stat.backlog_len,
+    },
+];
+
+impl Metric {
+    /// The help text of the metric, taken from the description of its field.
+    fn help(&self) -> &'static str {
+        FIELDS
+            .iter()
+            .find(|field| field.name == self.field)
+            .map_or("", |field| field.description)
+    }
+}
+
+fn prometheus<W>(mut out: W, rows: &[Row], options: &OutputOptions) -> io::Result<()>
+where
+    W: io::Write,
+{
+    let cpu_label = &options.cpu_label;
+    let labels: Vec<String> = rows.iter().map(|row| cpu_label.render(row.cpu())).collect();
+
+    // Every sample of a metric has to follow its HELP and TYPE lines, so the output is grouped by
+    // metric rather than by cpu.
+    let samples = |value: &dyn Fn(&SoftnetStat) -> Option<f64>| -> Vec<(&str, f64)> {
+        rows.iter()
+            .zip(labels.iter())
+            .filter_map(|(row, label)| Some((label.as_str(), value(&row.stat)?)))
+            .collect()
+    };
+
+    let prefix = &options.metric_prefix;
+    for metric in METRICS {
+        let samples = samples(&|stat| (metric.value)(stat).map(f64::from));
+        write_metric(
+            &mut out,
+            &prefix.name(metric.name),
+            metric.help(),
+            metric.kind,
+            &samples,
+        )?;
+    }
+
+    // Offline CPUs are not dumped at all, so every line that reports its cpu_id is online.
+    write_metric(
+        &mut out,
+        &prefix.name("cpu_online"),
+        "Whether the CPU is online, for kernels that report cpu_id.",
+        "gauge",
+        &samples(&|stat| stat.cpu_id.map(|_| 1.0)),
+    )?;
+
+    if options.frames_per_squeeze {
+        write_metric(
+            &mut out,
+            &prefix.name("frames_per_squeeze"),
+            "The average number of frames processed between each time the net_rx_action budget ran out.",
+            "gauge",
+            &samples(&frames_per_squeeze),
+        )?;
+    }
+
+    let build_info = prefix.name("stat_build_info");
+    metric_header(
+        &mut out,
+        &build_info,
+        "The version of softnet-stat that wrote these metrics.",
+        "gauge",
+    )?;
+    writeln!(
+        out,
+        "{}{{version=\"{}\"}} 1",
+        build_info,
+        env!("CARGO_PKG_VERSION")
+    )
+}
+
+fn metric_header<W>(mut out: W, name: &str, help: &str, kind: &str) -> io::Result<()>
+where
+    W: io::Write,
+{
+    writeln!(out, "# HELP {} {}", name, help)?;
+    writeln!(out, "# TYPE {} {}", name, kind)
+}
+
+/// Write a metric and its labelled samples, or nothing at all if it has no samples so that a field
+/// the kernel does not report can be told apart from one that is zero.
+fn write_metric<W>(
+    mut out: W,
+    name: &str,
+    help: &str,
+    kind: &str,
+    samples: &[(&str, f64)],
+) -> io::Result<()>
+where
+    W: io::Write,
+{
+    if samples.is_empty() {
+        return Ok(());
+    }
+
+    metric_header(&mut out, name, help, kind)?;
+    for (label, value) in samples {
+        writeln!(out, "{}{{{}}} {}", name, label, value)?;
+    }
+
+    Ok(())
+}
+
+/// Summarize every CPU on a single line, without a trailing newline, for status bars.
+fn statusline<W>(mut out: W, rows: &[Row]) -> io::Result<()>
+where
+    W: io::Write,
+{
+    // Unlike --total, the backlogs are added up, for how many frames are waiting on the whole
+    // machine.
+    let drops = rows
+        .iter()
+        .map(|row| row.stat.dropped)
+        .fold(0, add_counters);
+    let squeeze = rows
+        .iter()
+        .map(|row| row.stat.time_squeeze)
+        .fold(0, add_counters);
+    let backlog = rows
+        .iter()
+        .map(|row| row.stat.backlog_len)
+        .fold(None, add_optional_counters);
+
+    write!(out, "drops:{} squeeze:{}", drops, squeeze)?;
+    if let Some(backlog) = backlog {
+        write!(out, " backlog:{}", backlog)?;
+    }
+
+    Ok(())
+}
+
+/// Write one InfluxDB line protocol point per CPU, each stamped with `timestamp` in nanoseconds.
+///
+/// The measurement is the metric prefix, and the CPU is tagged with the same label as in the
+/// prometheus output. Fields the kernel does not report are left out rather than written as zero.
+fn influx<W>(mut out: W, rows: &[Row], options: &OutputOptions, timestamp: u128) -> io::Result<()>
+where
+    W: io::Write,
+{
+    let cpu_label = &options.cpu_label;
+
+    for row in rows {
+        let stat = &row.stat;
+
+        write!(
+            out,
+            "{},{}={}",
+            options.metric_prefix.0,
+            cpu_label.name,
+            escape_influx_tag(&cpu_label.value(row.cpu()))
+        )?;
+
+        let fields: Vec<String> = [
+            ("processed", Some(stat.processed)),
+            ("dropped", Some(stat.dropped)),
+            ("time_squeeze", Some(stat.time_squeeze)),
+            ("throttled", Some(stat.throttled)),
+            ("fastroute_hit", Some(stat.fastroute_hit)),
+            ("fastroute_success", Some(stat.fastroute_success)),
+            ("fastroute_defer", Some(stat.fastroute_defer)),
+            ("fastroute_deferred_out", Some(stat.fastroute_deferred_out)),
+            ("cpu_collision", Some(stat.cpu_collision)),
+            ("received_rps", stat.received_rps),
+            ("flow_limit_count", stat.flow_limit_count),
+            ("backlog_len", stat.backlog_len),
+        ]
+        .iter()
+        .filter_map(|(name, value)| Some(format!("{}={}i", name, (*value)?)))
+        .collect();
+
+        writeln!(out, " {} {}", fields.join(","), timestamp)?;
+    }
+
+    Ok(())
+}
+
+/// Write a statsd line for each metric of each CPU, named like `softnet.cpu3.dropped`.
+///
+/// The counters are sent as counters and the backlog length as a gauge. Unlike prometheus,
+/// statsd has no labels, so the CPU is part of the name. Fields the kernel does not report are left out.
+fn statsd<W>(mut out: W, rows: &[Row], options: &OutputOptions) -> io::Result<()>
+where
+    W: io::Write,
+{
+    for row in rows {
+        let name = format!(
+            "{}.{}",
+            options.metric_prefix.0,
+            escape_statsd(&options.cpu_label.value(row.cpu()))
+        );
+
+        let stat = &row.stat;
+        let metrics = [
+            ("processed", Some(stat.processed), "c"),
+            ("dropped", Some(stat.dropped), "c"),
+            ("time_squeeze", Some(stat.time_squeeze), "c"),
+            ("cpu_collision", Some(stat.cpu_collision), "c"),
+            ("received_rps", stat.received_rps, "c"),
+            ("flow_limit_count", stat.flow_limit_count, "c"),
+            ("backlog_len", stat.backlog_len, "g"),
+        ];
+        for (field, value, kind) in metrics {
+            if let Some(value) = value {
+                writeln!(out, "{}.{}:{}|{}", name, field, value, kind)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Replace the characters that separate the parts of a statsd line, and of its name.
+fn escape_statsd(value: &str) -> String {
+    value.replace(['.', ':', '|', '@', ' '], "_")
+}
+
+/// Escape the commas, equals signs and spaces in an InfluxDB tag value.
+fn escape_influx_tag(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, ',' | '=' | ' ') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+
+    escaped
+}
+
+/// Write one file per CPU, named `cpuN.<ext>`, into `dir`.
+fn split_by_cpu(
+    dir: &Path,
+    format: Format,
+    rows: &[Row],
+    options: &OutputOptions,
+) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+
+    for row in rows {
+        let path = dir.join(format!("cpu{}.{}", row.cpu(), format.extension()));
+        write_atomic(&path, |file| {
+            format.write(file, std::slice::from_ref(row), options)
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Write a file by writing to a temporary file next to it and renaming it into place, so readers
+/// never see a partially written file.
+fn write_atomic<F>(path: &Path, write: F) -> io::Result<()>
+where
+    F: FnOnce(&mut File) -> io::Result<()>,
+{
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?;
+    let tmp = path.with_file_name(format!(".{}.tmp{}", name.to_string_lossy(), process::id()));
+
+    let result = File::create(&tmp).and_then(|mut file| {
+        write(&mut file)?;
+        file.sync_all()
+    });
+
+    match result.and_then(|_| fs::rename(&tmp, path)) {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            let _ = fs::remove_file(&tmp);
+            Err(e)
+        }
+    }
+}
+
+#[test]
+fn test_split_by_cpu() {
+    let pwd = env!("CARGO_MANIFEST_DIR");
+    let handle = File::open(format!("{}/tests/proc-net-softnet_stat-2_6_32", pwd)).unwrap();
+    let raw = read_proc_file(handle).unwrap();
+    let (_, stats) = parse_softnet_stats(&raw).unwrap();
+    let rows = into_rows(stats);
+
+    let dir = env::temp_dir().join(format!("softnet-stat-split-{}", process::id()));
+    split_by_cpu(&dir, Format::Prometheus, &rows, &OutputOptions::default()).unwrap();
+
+    let mut names: Vec<String> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+    let cpu1 = fs::read_to_string(dir.join("cpu1.prom")).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    let expected: Vec<String> = (0..rows.len()).map(|i| format!("cpu{}.prom", i)).collect();
+    assert_eq!(expected, names);
+    assert!(cpu1.starts_with("# HELP softnet_frames_processed "));
+    assert!(cpu1.contains("\nsoftnet_frames_processed{cpu=\"cpu1\"} 1863193957\n"));
+}
+
+#[test]
+fn test_parse_sort_keys() {
+    assert_eq!(
+        Ok(vec![
+            SortKey {
+                field: SortField::Dropped,
+                descending: true,
+            },
+            SortKey {
+                field: SortField::Processed,
+                descending: false,
+            },
+            SortKey {
+                field: SortField::Cpu,
+                descending: false,
+            },
+        ]),
+        parse_sort_keys("dropped,processed:asc,cpu")
+    );
+    assert!(parse_sort_keys("bogus").is_err());
+    assert!(parse_sort_keys("dropped:sideways").is_err());
+}
+
+#[test]
+fn test_sort_rows() {
+    let raw = b"00000001 00000000 00000002 00000000 00000000 00000000 00000000 00000000 00000000
+00000003 00000001 00000002 00000000 00000000 00000000 00000000 00000000 00000000
+00000002 00000000 00000002 00000000 00000000 00000000 00000000 00000000 00000000
+00000002 00000000 00000005 00000000 00000000 00000000 00000000 00000000 00000000
+";
+    let (_, stats) = parse_softnet_stats(&raw[..]).unwrap();
+    let mut rows = into_rows(stats);
+
+    sort_rows(
+        &mut rows,
+        &parse_sort_keys("dropped,time_squeeze:asc").unwrap(),
+    );
+    let order: Vec<u32> = rows.iter().map(Row::cpu).collect();
+    assert_eq!(vec![1, 0, 2, 3], order);
+
+    sort_rows(&mut rows, &parse_sort_keys("cpu").unwrap());
+    let order: Vec<u32> = rows.iter().map(Row::cpu).collect();
+    assert_eq!(vec![0, 1, 2, 3], order);
+}
+
+#[test]
+fn test_sort_rows_optional() {
+    // Only the second and fourth lines report a backlog length.
+    let raw = b"00000001 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000
+00000001 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000001
+00000001 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000
+00000001 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000004 00000003
+";
+    let (_, stats) = parse_softnet_stats(&raw[..]).unwrap();
+    let mut rows = into_rows(stats);
+
+    // A missing value sorts below every value, and ties keep cpu order.
+    sort_rows(&mut rows, &parse_sort_keys("backlog_len").unwrap());
+    let order: Vec<u32> = rows.iter().map(Row::cpu).collect();
+    assert_eq!(vec![3, 1, 0, 2], order);
+
+    sort_rows(&mut rows, &parse_sort_keys("backlog_len:asc").unwrap());
+    let order: Vec<u32> = rows.iter().map(Row::cpu).collect();
+    assert_eq!(vec![0, 2, 1, 3], order);
+}
+
+#[test]
+fn test_statusline() {
+    let raw = b"00067f83 00000002 00000383 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000004 00000000
+00006c62 00000001 000000c1 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000008 00000001
+";
+    let (_, stats) = parse_softnet_stats(&raw[..]).unwrap();
+    let rows = into_rows(stats);
+
+    let mut out = Vec::new();
+    statusline(&mut out, &rows).unwrap();
+    assert_eq!(
+        "drops:3 squeeze:1092 backlog:12",
+        String::from_utf8(out).unwrap()
+    );
+
+    let raw = b"00067f83 00000002 00000383 00000000 00000000 00000000 00000000 00000000 00000000\n";
+    let (_, stats) = parse_softnet_stats(&raw[..]).unwrap();
+    let rows = into_rows(stats);
+
+    let mut out = Vec::new();
+    statusline(&mut out, &rows).unwrap();
+    assert_eq!("drops:2 squeeze:899", String::from_utf8(out).unwrap());
+
+    let rows = into_rows(vec![
+        SoftnetStat::new(0, u32::MAX, 1),
+        SoftnetStat::new(0, 1, 1),
+    ]);
+    let mut out = Vec::new();
+    statusline(&mut out, &rows).unwrap();
+    assert_eq!(
+        "drops:4294967295 squeeze:2",
+        String::from_utf8(out).unwrap()
+    );
+}
+
+#[test]
+fn test_cpu_label() {
+    assert_eq!("cpu=\"cpu3\"", CpuLabel::default().render(3));
+    assert_eq!("cpu=\"3\"", CpuLabel::new("cpu", "{id}").unwrap().render(3));
+    assert_eq!(
+        "core=\"core\\\"3\"",
+        CpuLabel::new("core", "core\"{id}").unwrap().render(3)
+    );
+
+    assert!(CpuLabel::new("0cpu", "{id}").is_err());
+    assert!(CpuLabel::new("__cpu", "{id}").is_err());
+    assert!(CpuLabel::new("cpu-id", "{id}").is_err());
+    assert!(CpuLabel::new("cpu", "cpu").is_err());
+}
+
+#[test]
+fn test_json_with_metadata() {
+    let raw = b"6dcad223 00000000 00000001 00000000 00000000 00000000 00000000 00000000 00000000\n";
+    let (_, stats) = parse_softnet_stats(&raw[..]).unwrap();
+    let rows = into_rows(stats);
+
+    let mut out = Vec::new();
+    json(&mut out, &rows, true, false, None).unwrap();
+    let value: serde_json::Value = serde_json::from_slice(&out).unwrap();
+
+    let fields = value["meta"]["fields"].as_array().unwrap();
+    let names: Vec<&str> = fields.iter().map(|f| f["name"].as_str().unwrap()).collect();
+    assert_eq!(
+        vec![
+            "processed",
+            "dropped",
+            "time_squeeze",
+            "throttled",
+            "fastroute_hit",
+            "fastroute_success",
+            "fastroute_defer",
+            "fastroute_deferred_out",
+            "cpu_collision",
+            "received_rps",
+            "flow_limit_count",
+            "backlog_len",
+            "cpu_id"
+        ],
+        names
+    );
+    assert_eq!("v4.7", fields[8]["removed"]);
+    assert_eq!("v5.10", fields[11]["since"]);
+    assert_eq!(None, fields[0].get("since"));
+    assert_eq!(1842008611, value["stats"][0]["processed"]);
+}
+
+#[test]
+fn test_drop_percent() {
+    let raw = b"00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000
+00000003 00000001 00000000 00000000 00000000 00000000 00000000 00000000 00000000
+";
+    let (_, stats) = parse_softnet_stats(&raw[..]).unwrap();
+
+    assert_eq!(0.0, drop_percent(&stats[0]));
+    assert_eq!(25.0, drop_percent(&stats[1]));
+
+    let mut out = Vec::new();
+    print(&mut out, &into_rows(stats), &OutputOptions::default()).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    let lines: Vec<Vec<&str>> = out
+        .lines()
+        .map(|line| line.split_whitespace().collect())
+        .collect();
+
+    assert_eq!(vec!["Dropped", "Drop", "%"], lines[0][2..5].to_vec());
+    assert_eq!("0.00", lines[1][3]);
+    assert_eq!("25.00", lines[2][3]);
+}
+
+#[test]
+fn test_frames_per_squeeze() {
+    let raw = b"00000064 00000000 00000008 00000000 00000000 00000000 00000000 00000000 00000000
+00000064 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000
+";
+    let (_, stats) = parse_softnet_stats(&raw[..]).unwrap();
+
+    assert_eq!(Some(12.5), frames_per_squeeze(&stats[0]));
+    assert_eq!(None, frames_per_squeeze(&stats[1]));
+
+    let rows = into_rows(stats);
+    let options = OutputOptions {
+        frames_per_squeeze: true,
+        ..OutputOptions::default()
+    };
+    let mut out = Vec::new();
+    prometheus(&mut out, &rows, &options).unwrap();
+    let out = String::from_utf8(out).unwrap();
+
+    assert!(out.contains("softnet_frames_per_squeeze{cpu=\"cpu0\"} 12.5\n"));
+    assert!(!out.contains("softnet_frames_per_squeeze{cpu=\"cpu1\"}"));
+}
+
+#[test]
+fn test_parse_interval() {
+    assert_eq!(Ok(Duration::from_secs(2)), parse_interval("2"));
+    assert_eq!(Ok(Duration::from_millis(1500)), parse_interval("1.5"));
+    assert_eq!(Ok(MIN_INTERVAL), parse_interval("0.001"));
+    assert!(parse_interval("0").is_err());
+    assert!(parse_interval("-1").is_err());
+    assert!(parse_interval("inf").is_err());
+    assert!(parse_interval("soon").is_err());
+}
+
+#[test]
+fn test_read_snapshot() {
+    let mut input = &b"\n00000001 00000000\n00000002 00000000\n\n00000003 00000000\n"[..];
+
+    assert_eq!(
+        Some(b"00000001 00000000\n00000002 00000000\n".to_vec()),
+        read_snapshot(&mut input).unwrap()
+    );
+    assert_eq!(
+        Some(b"00000003 00000000\n".to_vec()),
+        read_snapshot(&mut input).unwrap()
+    );
+    assert_eq!(None, read_snapshot(&mut input).unwrap());
+}
+
+#[test]
+fn test_parse_delimiter() {
+    assert_eq!(Ok(b','), parse_delimiter(","));
+    assert_eq!(Ok(b'|'), parse_delimiter("|"));
+    assert!(parse_delimiter("").is_err());
+    assert!(parse_delimiter(",,").is_err());
+    assert!(parse_delimiter("a").is_err());
+    assert!(parse_delimiter("\n").is_err());
+    assert!(parse_delimiter("é").is_err());
 }
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
-    let program = args[0].clone();
+#[test]
+fn test_replace_delimiter() {
+    let mut raw =
+        b"6dcad223|00000000|00000001|00000000|00000000|00000000|00000000|00000000|00000000\n"
+            .to_vec();
+    replace_delimiter(&mut raw, b'|');
 
-    let mut opts = Options::new();
-    opts.optflag("j", "json", "use json output");
-    opts.optflag("p", "prometheus", "use prometheus output");
-    opts.optflag("h", "help", "print this help menu");
-    opts.optflag("s", "stdin", "read from stdin");
+    let (_, stats) = parse_softnet_stats(&raw).unwrap();
+    assert_eq!(1, stats.len());
+    assert_eq!(1842008611, stats[0].processed);
+    assert_eq!(1, stats[0].time_squeeze);
+}
+
+#[test]
+fn test_bench() {
+    let raw = b"6dcad223 00000000 00000001 00000000 00000000 00000000 00000000 00000000 00000000\n";
+
+    let (elapsed, lines) = bench(&raw[..], 10);
+    assert!(elapsed > Duration::ZERO);
+    assert_eq!(10, lines);
+}
+
+#[test]
+fn test_bench_read() {
+    let pwd = env!("CARGO_MANIFEST_DIR");
+    let file = format!("{}/tests/proc-net-softnet_stat-5_10_47", pwd);
+    let len = fs::read(&file).unwrap().len();
+
+    let (_, bytes) = bench_read(&file, 3).unwrap();
+    assert_eq!(3 * len, bytes);
+    assert!(bench_read("/nonexistent", 1).is_err());
+}
+
+#[test]
+fn test_bench_rate() {
+    assert_eq!("2000", bench_rate(2, Duration::from_millis(1)));
+    assert_eq!("-", bench_rate(2, Duration::ZERO));
+}
+
+#[test]
+fn test_single_cpu() {
+    let pwd = env!("CARGO_MANIFEST_DIR");
+    let file = format!("{}/tests/proc-net-softnet_stat-5_10_47-single_cpu", pwd);
+    let raw = read_proc_file(File::open(file).unwrap()).unwrap();
+    let (remaining, stats) = parse_softnet_stats(&raw).unwrap();
+
+    assert!(remaining.is_empty());
+    assert_eq!(1, stats.len());
+    assert_eq!(None, inconsistent_line(&stats));
+    assert_eq!(Some(7262.117647058823), frames_per_squeeze(&stats[0]));
+
+    let rows = into_rows(stats);
+    let options = OutputOptions::default();
+
+    let mut out = Vec::new();
+    Format::Table.write(&mut out, &rows, &options).unwrap();
+    let table = String::from_utf8(out).unwrap();
+    let lines: Vec<&str> = table.lines().collect();
+    assert_eq!(2, lines.len());
+    assert_eq!(
+        vec!["0", "123456", "0", "0.00", "17"],
+        lines[1].split_whitespace().collect::<Vec<_>>()[..5].to_vec()
+    );
+
+    let mut out = Vec::new();
+    Format::Json.write(&mut out, &rows, &options).unwrap();
+    let json: Vec<SoftnetStat> = serde_json::from_slice(&out).unwrap();
+    assert_eq!(vec![rows[0].stat.clone()], json);
+
+    let mut out = Vec::new();
+    Format::Statusline.write(&mut out, &rows, &options).unwrap();
+    assert_eq!(
+        "drops:0 squeeze:17 backlog:0",
+        String::from_utf8(out).unwrap()
+    );
+}
+
+#[test]
+fn test_merge_duplicates() {
+    let raw = b"00000001 00000000 00000002 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000001 00000000
+00000003 00000001 00000002 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000002 00000001
+00000002 00000000 00000002 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000003 00000000
+ffffffff 00000000 00000005 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000004 00000001
+";
+    let (_, stats) = parse_softnet_stats(&raw[..]).unwrap();
+
+    let (rows, count) = merge_duplicates(into_rows(stats));
+    assert_eq!(2, count);
+    assert_eq!(2, rows.len());
+
+    assert_eq!(0, rows[0].cpu());
+    assert_eq!(3, rows[0].stat.processed);
+    assert_eq!(4, rows[0].stat.time_squeeze);
+    assert_eq!(Some(3), rows[0].stat.backlog_len);
+
+    assert_eq!(1, rows[1].cpu());
+    assert_eq!(u32::MAX, rows[1].stat.processed);
+    assert_eq!(1, rows[1].stat.dropped);
+    assert_eq!(Some(4), rows[1].stat.backlog_len);
+}
+
+#[test]
+fn test_merge_duplicates_without_cpu_id() {
+    let raw = b"00000001 00000000 00000002 00000000 00000000 00000000 00000000 00000000 00000000
+00000001 00000000 00000002 00000000 00000000 00000000 00000000 00000000 00000000
+";
+    let (_, stats) = parse_softnet_stats(&raw[..]).unwrap();
+
+    let (rows, count) = merge_duplicates(into_rows(stats));
+    assert_eq!(0, count);
+    assert_eq!(2, rows.len());
+}
+
+#[test]
+fn test_missing_cpu_id() {
+    let pwd = env!("CARGO_MANIFEST_DIR");
+
+    let file = format!("{}/tests/proc-net-softnet_stat-5_10_47", pwd);
+    let raw = read_proc_file(File::open(file).unwrap()).unwrap();
+    let (_, stats) = parse_softnet_stats(&raw).unwrap();
+    assert_eq!(None, missing_cpu_id(&stats));
+
+    let file = format!("{}/tests/proc-net-softnet_stat-3_11", pwd);
+    let raw = read_proc_file(File::open(file).unwrap()).unwrap();
+    let (_, stats) = parse_softnet_stats(&raw).unwrap();
+    assert_eq!(Some(0), missing_cpu_id(&stats));
+}
+
+#[test]
+fn test_prometheus_build_info() {
+    let raw = b"6dcad223 00000000 00000001 00000000 00000000 00000000 00000000 00000000 00000000\n";
+    let (_, stats) = parse_softnet_stats(&raw[..]).unwrap();
+    let rows = into_rows(stats);
+
+    let mut out = Vec::new();
+    prometheus(&mut out, &rows, &OutputOptions::default()).unwrap();
+    let out = String::from_utf8(out).unwrap();
+
+    let build_info = format!(
+        "softnet_stat_build_info{{version=\"{}\"}} 1\n",
+        env!("CARGO_PKG_VERSION")
+    );
+    assert_eq!(1, out.matches(&build_info).count());
+}
+
+#[test]
+fn test_check_thresholds() {
+    let raw = b"00000001 00000000 00000064 00000000 00000000 00000000 00000000 00000000 00000000
+00000001 00000002 00000064 00000000 00000000 00000000 00000000 00000000 00000000
+00000001 00000000 000001f4 00000000 00000000 00000000 00000000 00000000 00000000
+";
+    let (_, stats) = parse_softnet_stats(&raw[..]).unwrap();
+    let rows = into_rows(stats);
+
+    let thresholds: Thresholds = serde_json::from_str(
+        r#"{"default": {"dropped": 0, "time_squeeze": 100}, "cpus": {"2": {"time_squeeze": 1000}}}"#,
+    )
+    .unwrap();
+    assert_eq!(
+        vec![Violation {
+            cpu: 1,
+            field: "dropped",
+            value: 2,
+            limit: 0,
+        }],
+        check_thresholds(&rows, &thresholds)
+    );
 
-    let matches = match opts.parse(&args[1..]) {
-        Ok(m) => m,
-        Err(e) => panic!("Failed to parse options - {}", e),
+    let thresholds: Thresholds =
+        serde_json::from_str(r#"{"cpus": {"0": {"time_squeeze": 99}}}"#).unwrap();
+    assert_eq!(
+        vec![Violation {
+            cpu: 0,
+            field: "time_squeeze",
+            value: 100,
+            limit: 99,
+        }],
+        check_thresholds(&rows, &thresholds)
+    );
+
+    assert!(serde_json::from_str::<Thresholds>(r#"{"default": {"drops": 0}}"#).is_err());
+}
+
+#[test]
+fn test_human_count() {
+    assert_eq!("0", human_count(0));
+    assert_eq!("999", human_count(999));
+    assert_eq!("1.0K", human_count(1000));
+    assert_eq!("12.3K", human_count(12_345));
+    assert_eq!("1.0M", human_count(999_950));
+    assert_eq!("1.8G", human_count(1_842_008_611));
+    assert_eq!("4.3G", human_count(u32::MAX));
+}
+
+#[test]
+fn test_json_plain() {
+    let raw = b"6dcad223 00000000 00000001 00000000 00000000 00000000 00000000 00000000 00000000\n";
+    let (_, stats) = parse_softnet_stats(&raw[..]).unwrap();
+    let expected = serde_json::to_string(&stats).unwrap();
+
+    let mut out = Vec::new();
+    json(&mut out, &into_rows(stats), false, false, None).unwrap();
+    assert_eq!(format!("{}\n", expected), String::from_utf8(out).unwrap());
+}
+
+#[test]
+fn test_dump_layout() {
+    let line = b"00067f83 00000000 00000383 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000001 0000zz00";
+
+    let mut out = Vec::new();
+    dump_layout(&mut out, &line[..]).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    let lines: Vec<&str> = out.lines().collect();
+
+    assert_eq!(15, lines.len());
+    assert_eq!(
+        "0         processed               00067f83  425859",
+        lines[1]
+    );
+    assert_eq!("7         fastroute_deferred_out  00000000  0", lines[8]);
+    assert_eq!("8         cpu_collision           00000000  0", lines[9]);
+    assert_eq!("12        cpu_id                  00000001  1", lines[13]);
+    assert_eq!(
+        "13        (unknown)               0000zz00  (not hex)",
+        lines[14]
+    );
+}
+
+#[test]
+fn test_rates() {
+    let prev = b"00000064 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000
+000000c8 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000002
+";
+    // cpu 1 has come online and the line for cpu 2 has moved down.
+    let cur = b"000000c8 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000
+00000005 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000001
+0000012c 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000002
+";
+    let (_, prev) = parse_softnet_stats(&prev[..]).unwrap();
+    let (_, cur) = parse_softnet_stats(&cur[..]).unwrap();
+
+    let rates = rates(&into_rows(prev), &into_rows(cur), 2.0);
+    assert_eq!(2, rates.len());
+    assert_eq!(Some(0), rates[0].rate.cpu_id);
+    assert_eq!(50.0, rates[0].rate.processed);
+    assert_eq!(Some(2), rates[1].rate.cpu_id);
+    assert_eq!(2, rates[1].index);
+    assert_eq!(50.0, rates[1].rate.processed);
+}
+
+#[test]
+fn test_prometheus_help_and_type() {
+    let raw = b"6dcad223 00000000 00000001 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000
+6f0e1565 00000000 00000002 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000001
+";
+    let (_, stats) = parse_softnet_stats(&raw[..]).unwrap();
+    let rows = into_rows(stats);
+    let options = OutputOptions {
+        frames_per_squeeze: true,
+        ..OutputOptions::default()
     };
 
-    if matches.opt_present("h") {
-        print_usage(&program, opts);
-        return;
+    let mut out = Vec::new();
+    prometheus(&mut out, &rows, &options).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    let lines: Vec<&str> = out.lines().collect();
+
+    let mut names: Vec<String> = METRICS
+        .iter()
+        .map(|metric| format!("softnet_{}", metric.name))
+        .collect();
+    names.push("softnet_cpu_online".to_string());
+    names.push("softnet_frames_per_squeeze".to_string());
+    names.push("softnet_stat_build_info".to_string());
+
+    for name in names {
+        let help = format!("# HELP {} ", name);
+        let kind = format!("# TYPE {} ", name);
+        let sample = format!("{}{{", name);
+
+        assert_eq!(1, lines.iter().filter(|l| l.starts_with(&help)).count());
+        assert_eq!(1, lines.iter().filter(|l| l.starts_with(&kind)).count());
+
+        let help_at = lines.iter().position(|l| l.starts_with(&help)).unwrap();
+        assert!(lines[help_at + 1].starts_with(&kind));
+        assert!(
+            !lines[help_at].ends_with(&help),
+            "{} has no help text",
+            name
+        );
+
+        // The samples immediately follow the header, with none for this metric anywhere else.
+        let samples = lines.iter().filter(|l| l.starts_with(&sample)).count();
+        assert!(samples > 0);
+        assert!(lines[help_at + 2..help_at + 2 + samples]
+            .iter()
+            .all(|l| l.starts_with(&sample)));
     }
 
-    let file = "/proc/net/softnet_stat";
+    assert!(out.contains("# TYPE softnet_frames_processed counter\n"));
+    assert!(out.contains("# TYPE softnet_backlog_len gauge\n"));
+}
+
+#[test]
+fn test_prometheus_optional_metrics() {
+    let pwd = env!("CARGO_MANIFEST_DIR");
+    let metrics = |name: &str| {
+        let handle = File::open(format!("{}/tests/{}", pwd, name)).unwrap();
+        let (_, stats) = parse_softnet_stats(&read_proc_file(handle).unwrap()).unwrap();
 
-    let raw = if matches.opt_present("s") {
-        let handle = io::stdin();
-        read_proc_file(handle).expect("Failed to read proc from stdin")
-    } else {
-        let handle = File::open(file).expect("Failed to open file");
-        read_proc_file(handle).expect("Failed to read proc from file")
+        let mut out = Vec::new();
+        prometheus(&mut out, &into_rows(stats), &OutputOptions::default()).unwrap();
+        String::from_utf8(out).unwrap()
     };
 
-    let stats = match parse_softnet_stats(&raw) {
-        Ok((_, value)) => value,
-        Err(Err::Incomplete(needed)) => {
-            panic!("{} is in an unsupported format. Needed: {:?}", file, needed)
-        }
-        Err(Err::Error(e)) | Err(Err::Failure(e)) => {
-            panic!("Error while parsing {}: {:?}", file, e)
-        }
+    // v2.6.32 reports neither received_rps nor flow_limit_count, so neither is written as zero.
+    let oldest = metrics("proc-net-softnet_stat-2_6_32");
+    assert!(!oldest.contains("softnet_received_rps"));
+    assert!(!oldest.contains("softnet_flow_limit_count"));
+    assert!(oldest.contains("softnet_cpu_collisions{cpu=\"cpu0\"} 0\n"));
+
+    let old = metrics("proc-net-softnet_stat-3_11");
+    assert!(old.contains("softnet_flow_limit_count{cpu=\"cpu0\"} 0\n"));
+    assert!(!old.contains("softnet_backlog_len"));
+    assert!(!old.contains("softnet_cpu_online"));
+
+    let new = metrics("proc-net-softnet_stat-5_10_47");
+    assert!(new.contains("# TYPE softnet_backlog_len gauge\n"));
+    assert!(new.contains("softnet_backlog_len{cpu=\"cpu0\"} 0\n"));
+    assert!(new.contains("# TYPE softnet_cpu_online gauge\n"));
+    assert!(new.contains("softnet_cpu_online{cpu=\"cpu1\"} 1\n"));
+}
+
+#[test]
+fn test_print_total() {
+    let raw = b"00000001 00000000 00000002 00000000 00000000 00000000 00000000 00000000 00000000
+00000003 00000001 00000004 00000000 00000000 00000000 00000000 00000000 00000000
+";
+    let (_, stats) = parse_softnet_stats(&raw[..]).unwrap();
+    let rows = into_rows(stats);
+
+    let mut out = Vec::new();
+    let options = OutputOptions {
+        total: true,
+        ..OutputOptions::default()
     };
+    print(&mut out, &rows, &options).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    let lines: Vec<&str> = out.lines().collect();
 
-    if matches.opt_present("j") {
-        json(&stats);
-    } else if matches.opt_present("p") {
-        prometheus(&stats);
-    } else {
-        print(&stats, 15);
-    }
+    assert_eq!(5, lines.len());
+    assert_eq!("", lines[3]);
+    let total: Vec<&str> = lines[4].split_whitespace().collect();
+    assert_eq!(vec!["Total", "4", "1", "20.00", "6"], total[..5].to_vec());
 }
 
-fn read_proc_file<R>(mut handle: R) -> io::Result<Vec<u8>>
-where
-    R: io::Read,
-{
-    let mut buf = vec![];
-    handle.read_to_end(&mut buf)?;
+#[test]
+fn test_csv() {
+    let pwd = env!("CARGO_MANIFEST_DIR");
+    let handle = File::open(format!("{}/tests/proc-net-softnet_stat-3_11", pwd)).unwrap();
+    let (_, stats) = parse_softnet_stats(&read_proc_file(handle).unwrap()).unwrap();
+
+    let mut out = Vec::new();
+    csv(&mut out, &into_rows(stats)).unwrap();
+
+    let expected = "\
+cpu,processed,dropped,time_squeeze,cpu_collision,received_rps,flow_limit_count,backlog_len,cpu_id
+0,142307,0,0,0,0,0,,
+1,9685,0,0,0,0,0,,
+2,11468,0,0,0,0,0,,
+3,9607,0,0,0,0,0,,
+4,3769,0,0,0,0,0,,
+5,3439,0,0,0,0,0,,
+6,4149,0,0,0,0,0,,
+7,3251,0,0,0,0,0,,
+";
+    assert_eq!(expected, String::from_utf8(out).unwrap());
+}
+
+#[test]
+fn test_select_cpus() {
+    let raw = b"00000001 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000
+00000002 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000
+00000003 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000
+00000004 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000
+";
+    let (_, stats) = parse_softnet_stats(&raw[..]).unwrap();
+    let select = |spec| parse_cpus(spec).map(|cpus| select_cpus(&stats, &cpus));
+
+    assert_eq!(Ok(vec![2]), select("2"));
+    assert_eq!(Ok(vec![0, 1, 3]), select("0-1,3"));
+    assert_eq!(Ok(vec![1, 2]), select("2,1-2,1"));
+    assert_eq!(Ok(vec![]), select("8-15"));
+    assert!(select("3-1").is_err());
+    assert!(select("one").is_err());
+    assert!(select("").is_err());
+}
+
+#[test]
+fn test_select_cpus_by_cpu_id() {
+    // cpu 1 is offline, so cpu 2 is on the second line.
+    let raw = b"00000001 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000
+00000002 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000002
+";
+    let (_, stats) = parse_softnet_stats(&raw[..]).unwrap();
+    let cpus = |spec| parse_cpus(spec).unwrap();
 
-    Ok(buf)
+    assert_eq!(vec![1], select_cpus(&stats, &cpus("2")));
+    assert_eq!(Vec::<usize>::new(), select_cpus(&stats, &cpus("1")));
 }
 
-fn parse_softnet_stats(input: &[u8]) -> IResult<&[u8], Vec<SoftnetStat>> {
-    many1(parse_softnet_line)(input)
+#[test]
+fn test_json_pretty() {
+    let raw = b"6dcad223 00000000 00000001 00000000 00000000 00000000 00000000 00000000 00000000\n";
+    let (_, stats) = parse_softnet_stats(&raw[..]).unwrap();
+    let rows = into_rows(stats);
+
+    let mut compact = Vec::new();
+    json(&mut compact, &rows, false, false, None).unwrap();
+    let mut pretty = Vec::new();
+    json(&mut pretty, &rows, false, true, None).unwrap();
+
+    assert_eq!(1, compact.iter().filter(|b| **b == b'\n').count());
+    assert!(pretty.iter().filter(|b| **b == b'\n').count() > 1);
+    assert!(String::from_utf8(pretty.clone())
+        .unwrap()
+        .contains("\n    \"processed\": 1842008611"));
+
+    let compact: serde_json::Value = serde_json::from_slice(&compact).unwrap();
+    let pretty: serde_json::Value = serde_json::from_slice(&pretty).unwrap();
+    assert_eq!(compact, pretty);
 }
 
-fn parse_softnet_line(input: &[u8]) -> IResult<&[u8], SoftnetStat> {
-    if input.as_bytes().is_empty() {
-        return Err(Err::Error(Error::new(input, ErrorKind::Eof)));
+#[test]
+fn test_ndjson() {
+    let pwd = env!("CARGO_MANIFEST_DIR");
+    let handle = File::open(format!("{}/tests/proc-net-softnet_stat-3_11", pwd)).unwrap();
+    let (_, stats) = parse_softnet_stats(&read_proc_file(handle).unwrap()).unwrap();
+    let rows = into_rows(stats);
+
+    let mut out = Vec::new();
+    ndjson(&mut out, &rows).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    let lines: Vec<&str> = out.lines().collect();
+
+    assert_eq!(rows.len(), lines.len());
+    for (i, line) in lines.iter().enumerate() {
+        let value: serde_json::Value = serde_json::from_str(line).unwrap();
+        assert_eq!(i as u64, value["cpu"]);
+        assert_eq!(u64::from(rows[i].stat.processed), value["processed"]);
     }
+}
 
-    let line = tuple((
-        hex_u32,                  // processed
-        preceded(space, hex_u32), // dropped
-        preceded(space, hex_u32), // time_squeeze
-        preceded(space, hex_u32),
-        preceded(space, hex_u32),
-        preceded(space, hex_u32),
-        preceded(space, hex_u32),
-        preceded(space, hex_u32),
-        preceded(space, hex_u32),      // cpu collision
-        opt(preceded(space, hex_u32)), // received_rps
-        opt(preceded(space, hex_u32)), // flow_limit_count
-        opt(preceded(space, hex_u32)), // backlog_len
-        opt(preceded(space, hex_u32)), // cpu_id
-        line_ending,
-    ));
+#[test]
+fn test_metric_prefix() {
+    let raw = b"6dcad223 00000000 00000001 00000000 00000000 00000000 00000000 00000000 00000000\n";
+    let (_, stats) = parse_softnet_stats(&raw[..]).unwrap();
+    let options = OutputOptions {
+        metric_prefix: MetricPrefix::new("myorg_softnet").unwrap(),
+        ..OutputOptions::default()
+    };
 
-    let mut parser = map(line, |result| SoftnetStat {
-        processed: result.0,
-        dropped: result.1,
-        time_squeeze: result.2,
-        cpu_collision: result.8,
-        received_rps: result.9,
-        flow_limit_count: result.10,
-        backlog_len: result.11,
-        cpu_id: result.12,
-    });
+    let mut out = Vec::new();
+    prometheus(&mut out, &into_rows(stats), &options).unwrap();
+    let out = String::from_utf8(out).unwrap();
+
+    assert!(out.starts_with("# HELP myorg_softnet_frames_processed "));
+    assert!(out.contains("\n# TYPE myorg_softnet_frames_processed counter\n"));
+    assert!(out.contains("\nmyorg_softnet_frames_processed{cpu=\"cpu0\"} 1842008611\n"));
+    assert!(out.contains("\nmyorg_softnet_stat_build_info{version="));
+    assert!(out
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .all(|line| line.starts_with("myorg_softnet_")));
 
-    parser(input)
+    assert!(MetricPrefix::new("_private").is_ok());
+    assert!(MetricPrefix::new("9softnet").is_err());
+    assert!(MetricPrefix::new("my-org").is_err());
+    assert!(MetricPrefix::new("").is_err());
 }
 
-fn space(input: &[u8]) -> IResult<&[u8], char> {
-    char(' ')(input)
+#[test]
+fn test_print_color() {
+    let raw = b"00000001 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000
+00000001 00000001 00000001 00000000 00000000 00000000 00000000 00000000 00000000
+00000001 00000000 00000001 00000000 00000000 00000000 00000000 00000000 00000000
+";
+    let (_, stats) = parse_softnet_stats(&raw[..]).unwrap();
+    let rows = into_rows(stats);
+
+    let mut out = Vec::new();
+    let options = OutputOptions {
+        color: true,
+        ..OutputOptions::default()
+    };
+    print(&mut out, &rows, &options).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    let lines: Vec<&str> = out.lines().collect();
+
+    assert!(lines[0].starts_with("Cpu "));
+    assert!(lines[1].starts_with("0 "));
+    assert!(lines[2].starts_with("\x1b[31m1 ") && lines[2].ends_with("\x1b[0m"));
+    assert!(lines[3].starts_with("\x1b[33m2 ") && lines[3].ends_with("\x1b[0m"));
+
+    let mut out = Vec::new();
+    print(&mut out, &rows, &OutputOptions::default()).unwrap();
+    assert!(!out.contains(&0x1b));
 }
 
-fn print_usage(program: &str, opts: Options) {
-    let brief = format!("Usage: {} [options]", program);
-    print!("{}", opts.usage(&brief));
+#[test]
+fn test_print_diff() {
+    let (_, old) = parse_softnet_stats(
+        b"00000010 00000000 00000001 00000000 00000000 00000000 00000000 00000000 00000000
+00000010 00000004 00000001 00000000 00000000 00000000 00000000 00000000 00000000
+",
+    )
+    .unwrap();
+    let (_, new) = parse_softnet_stats(
+        b"00000018 00000000 00000003 00000000 00000000 00000000 00000000 00000000 00000000
+00000004 00000000 00000001 00000000 00000000 00000000 00000000 00000000 00000000
+00000001 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000
+",
+    )
+    .unwrap();
+
+    let mut out = Vec::new();
+    print_diff(&mut out, &diff(&old, &new), &OutputOptions::default()).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    let lines: Vec<&str> = out.lines().collect();
+
+    assert_eq!(4, lines.len());
+    assert!(lines[0].starts_with("Cpu  Processed  "));
+    assert!(lines[0].ends_with("Note"));
+    assert_eq!(
+        vec!["0", "8", "0", "2", "0", "-", "-", "0"],
+        lines[1].split_whitespace().collect::<Vec<_>>()
+    );
+    assert_eq!(
+        vec!["1", "-12", "-4", "0", "0", "-", "-", "0", "counter", "reset"],
+        lines[2].split_whitespace().collect::<Vec<_>>()
+    );
+    assert_eq!(
+        vec!["2", "only", "in", "NEW"],
+        lines[3].split_whitespace().collect::<Vec<_>>()
+    );
 }
 
-fn print(stats: &[SoftnetStat], spacer: usize) {
-    println!(
-        "{:<spacer$}{:<spacer$}{:<spacer$}{:<spacer$}{:<spacer$}{:<spacer$}{:<spacer$}{:<spacer$}{:<spacer$}",
-        "Cpu",
-        "Processed",
-        "Dropped",
-        "Time Squeezed",
-        "Cpu Collision",
-        "Received RPS",
-        "Flow Limit Cnt",
-        "Backlog Length",
-        "CPU Id",
-        spacer = spacer
+#[test]
+fn test_influx() {
+    let pwd = env!("CARGO_MANIFEST_DIR");
+    let handle = File::open(format!("{}/tests/proc-net-softnet_stat-5_10_47", pwd)).unwrap();
+    let (_, stats) = parse_softnet_stats(&read_proc_file(handle).unwrap()).unwrap();
+
+    let mut out = Vec::new();
+    influx(
+        &mut out,
+        &into_rows(stats),
+        &OutputOptions::default(),
+        1_600_000_000_000_000_000,
+    )
+    .unwrap();
+    let out = String::from_utf8(out).unwrap();
+
+    assert_eq!(
+        Some("softnet,cpu=cpu0 processed=425859i,dropped=0i,time_squeeze=899i,throttled=0i,fastroute_hit=0i,fastroute_success=0i,fastroute_defer=0i,fastroute_deferred_out=0i,cpu_collision=0i,received_rps=0i,flow_limit_count=0i,backlog_len=0i 1600000000000000000"),
+        out.lines().next()
     );
+    assert_eq!(2, out.lines().count());
 
-    for (i, stat) in stats.iter().enumerate() {
-        println!(
-            "{:<spacer$}{:<spacer$}{:<spacer$}{:<spacer$}{:<spacer$}{:<spacer$}{:<spacer$}{:<spacer$}{:<spacer$}",
-            i,
-            stat.processed,
-            stat.dropped,
-            stat.time_squeeze,
-            stat.cpu_collision,
-            stat.received_rps.unwrap_or_default(),
-            stat.flow_limit_count.unwrap_or_default(),
-            stat.backlog_len.unwrap_or_default(),
-            stat.cpu_id.unwrap_or_default(),
-            spacer = spacer
-        );
-    }
-}
+    // Fields that older kernels do not report are left out, and the measurement follows the
+    // metric prefix.
+    let raw = b"6dcad223 00000000 00000001 00000000 00000000 00000000 00000000 00000000 00000000\n";
+    let (_, stats) = parse_softnet_stats(&raw[..]).unwrap();
+    let rows = into_rows(stats);
+    let options = OutputOptions {
+        cpu_label: CpuLabel::new("core", "{id}").unwrap(),
+        metric_prefix: MetricPrefix::new("node").unwrap(),
+        ..OutputOptions::default()
+    };
 
-fn json(stats: &[SoftnetStat]) {
-    let data = serde_json::to_string(&stats).expect("Failed to encode stats into json format");
-    println!("{}", data);
+    let mut out = Vec::new();
+    influx(&mut out, &rows, &options, 1).unwrap();
+    assert_eq!(
+        "node,core=0 processed=1842008611i,dropped=0i,time_squeeze=1i,throttled=0i,fastroute_hit=0i,fastroute_success=0i,fastroute_defer=0i,fastroute_deferred_out=0i,cpu_collision=0i 1\n",
+        String::from_utf8(out).unwrap()
+    );
 }
 
-fn prometheus(stats: &[SoftnetStat]) {
-    for (i, stat) in stats.iter().enumerate() {
-        // Prior to Linux kernel v5.10, we used the index to determine the CPU Id. However, this is
-        // not always correct as offline CPUs are not reported in the softnet data. If we are on a
-        // Linux kernel that supports the cpu_id data, then we use that instead.
-        let cpu_id = stat.cpu_id.unwrap_or(i as u32);
+#[test]
+fn test_json_timestamp() {
+    let pwd = env!("CARGO_MANIFEST_DIR");
+    let handle = File::open(format!("{}/tests/proc-net-softnet_stat-5_10_47", pwd)).unwrap();
+    let (_, stats) = parse_softnet_stats(&read_proc_file(handle).unwrap()).unwrap();
+    let rows = into_rows(stats.clone());
 
-        println!(
-            "softnet_frames_processed{{cpu=\"cpu{}\"}} {}",
-            cpu_id, stat.processed
-        );
-        println!(
-            "softnet_frames_dropped{{cpu=\"cpu{}\"}} {}",
-            cpu_id, stat.dropped
-        );
-        println!(
-            "softnet_time_squeeze{{cpu=\"cpu{}\"}} {}",
-            cpu_id, stat.time_squeeze
-        );
-        println!(
-            "softnet_cpu_collisions{{cpu=\"cpu{}\"}} {}",
-            cpu_id, stat.cpu_collision
-        );
-        println!(
-            "softnet_received_rps{{cpu=\"cpu{}\"}} {}",
-            cpu_id,
-            stat.received_rps.unwrap_or_default()
-        );
-        println!(
-            "softnet_flow_limit_count{{cpu=\"cpu{}\"}} {}",
-            cpu_id,
-            stat.flow_limit_count.unwrap_or_default()
-        );
-        println!(
-            "softnet_backlog_len{{cpu=\"cpu{}\"}} {}",
-            cpu_id,
-            stat.backlog_len.unwrap_or_default()
-        );
-    }
+    let mut out = Vec::new();
+    json(&mut out, &rows, false, false, Some("2024-01-02T03:04:05Z")).unwrap();
+    let value: serde_json::Value = serde_json::from_slice(&out).unwrap();
+
+    assert_eq!("2024-01-02T03:04:05Z", value["timestamp"]);
+    assert!(value.get("meta").is_none());
+    let parsed: Vec<SoftnetStat> = serde_json::from_value(value["stats"].clone()).unwrap();
+    assert_eq!(stats, parsed);
+
+    let mut out = Vec::new();
+    json(&mut out, &rows, true, false, Some("2024-01-02T03:04:05Z")).unwrap();
+    let value: serde_json::Value = serde_json::from_slice(&out).unwrap();
+    assert_eq!("processed", value["meta"]["fields"][0]["name"]);
 }
 
 #[test]
-fn test_parse_softnet_empty_line() {
-    let raw = b"";
+fn test_rfc3339() {
+    let at = |secs| rfc3339(UNIX_EPOCH + Duration::from_secs(secs));
 
-    // FIXME
-    // Err(Err::Error((&raw[..] ErrorKind::Eof)))) should work, but there is some type inference
-    // issue going on
-    assert_eq!(parse_softnet_line(&raw[..]).is_err(), true,);
+    assert_eq!("1970-01-01T00:00:00Z", at(0));
+    assert_eq!("2024-01-02T03:04:05Z", at(1_704_164_645));
+    assert_eq!("2000-02-29T23:59:59Z", at(951_868_799));
 }
 
 #[test]
-fn test_parse_softnet_line() {
+fn test_statsd() {
+    let pwd = env!("CARGO_MANIFEST_DIR");
+    let handle = File::open(format!("{}/tests/proc-net-softnet_stat-5_10_47-drops", pwd)).unwrap();
+    let (_, stats) = parse_softnet_stats(&read_proc_file(handle).unwrap()).unwrap();
+    let rows = into_rows(stats);
+
+    let mut out = Vec::new();
+    statsd(&mut out, &rows[2..3], &OutputOptions::default()).unwrap();
+    assert_eq!(
+        "softnet.cpu2.processed:1837982|c
+softnet.cpu2.dropped:42|c
+softnet.cpu2.time_squeeze:4804|c
+softnet.cpu2.cpu_collision:0|c
+softnet.cpu2.received_rps:0|c
+softnet.cpu2.flow_limit_count:0|c
+softnet.cpu2.backlog_len:3|g
+",
+        String::from_utf8(out).unwrap()
+    );
+
     let raw = b"6dcad223 00000000 00000001 00000000 00000000 00000000 00000000 00000000 00000000\n";
+    let (_, stats) = parse_softnet_stats(&raw[..]).unwrap();
+    let rows = into_rows(stats);
+    let options = OutputOptions {
+        metric_prefix: MetricPrefix::new("node").unwrap(),
+        ..OutputOptions::default()
+    };
+
+    let mut out = Vec::new();
+    statsd(&mut out, &rows, &options).unwrap();
+    assert_eq!(
+        "node.cpu0.processed:1842008611|c
+node.cpu0.dropped:0|c
+node.cpu0.time_squeeze:1|c
+node.cpu0.cpu_collision:0|c
+",
+        String::from_utf8(out).unwrap()
+    );
+}
+
+#[test]
+fn test_print_widths() {
+    let raw = b"ffffffff 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000
+00000001 ffffffff 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000001
+";
+    let (_, stats) = parse_softnet_stats(&raw[..]).unwrap();
+    let rows = into_rows(stats);
 
-    let (remaining, value) = parse_softnet_line(&raw[..]).unwrap();
+    let mut out = Vec::new();
+    print(&mut out, &rows, &OutputOptions::default()).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    let lines: Vec<&str> = out.lines().collect();
 
-    assert_eq!(0, remaining.as_bytes().len());
+    // Every line has a field in each column, so splitting on whitespace finds them all only if
+    // no two fields touch.
     assert_eq!(
-        SoftnetStat {
-            processed: 1842008611,
-            dropped: 0,
-            time_squeeze: 1,
-            cpu_collision: 0,
-            received_rps: None,
-            flow_limit_count: None,
-            backlog_len: None,
-            cpu_id: None,
-        },
-        value
+        vec!["0", "4294967295", "0", "0.00", "0"],
+        lines[1].split_whitespace().collect::<Vec<_>>()[..5].to_vec()
+    );
+    assert_eq!(
+        vec!["1", "1", "4294967295", "100.00", "0"],
+        lines[2].split_whitespace().collect::<Vec<_>>()[..5].to_vec()
     );
+    assert_eq!(16, lines[1].split_whitespace().count());
+    // The columns line up with their headers.
+    for column in ["Processed", "Dropped", "Time Squeezed", "Frames/Squeeze"] {
+        let start = lines[0].find(column).unwrap();
+        assert_eq!(Some(' '), lines[1][..start].chars().last(), "{}", column);
+        assert_ne!(Some(' '), lines[1][start..].chars().next(), "{}", column);
+    }
+    assert!(lines.iter().all(|line| !line.ends_with(' ')));
+
+    let mut out = Vec::new();
+    let options = OutputOptions {
+        width: Some(15),
+        ..OutputOptions::default()
+    };
+    print(&mut out, &rows, &options).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.starts_with("Cpu            Processed      Dropped        Drop %  "));
 }
 
+#[cfg(feature = "yaml")]
 #[test]
-fn test_parse_softnet_stats() {
+fn test_yaml() {
     let pwd = env!("CARGO_MANIFEST_DIR");
-    let files = vec![
-        format!("{}/tests/proc-net-softnet_stat-2_6_32", pwd),
-        format!("{}/tests/proc-net-softnet_stat-2_6_36", pwd),
-        format!("{}/tests/proc-net-softnet_stat-3_11", pwd),
-        format!("{}/tests/proc-net-softnet_stat-5_10_47", pwd),
-    ];
+    let raw = fs::read(format!("{}/tests/proc-net-softnet_stat-4_19", pwd)).unwrap();
+    let (_, stats) = parse_softnet_stats(&raw).unwrap();
+    let rows = into_rows(stats.clone());
+
+    let mut out = Vec::new();
+    Format::Yaml
+        .write(&mut out, &rows, &OutputOptions::default())
+        .unwrap();
+    let out = String::from_utf8(out).unwrap();
 
-    for file in files.iter() {
-        let handle = File::open(file).unwrap();
-        let raw = read_proc_file(handle).unwrap();
+    // v4.19 does not report the backlog length or cpu id, so they are left out rather than null.
+    let expected = "- processed: 171744033
+  dropped: 0
+  time_squeeze: 446
+  throttled: 0
+  fastroute_hit: 0
+  fastroute_success: 0
+  fastroute_defer: 0
+  fastroute_deferred_out: 0
+  cpu_collision: 0
+  received_rps: 0
+  flow_limit_count: 0
+- processed: 165795015
+  dropped: 3
+  time_squeeze: 370
+  throttled: 0
+  fastroute_hit: 0
+  fastroute_success: 0
+  fastroute_defer: 0
+  fastroute_deferred_out: 0
+  cpu_collision: 0
+  received_rps: 0
+  flow_limit_count: 0
+- processed: 187166814
+  dropped: 0
+  time_squeeze: 681
+  throttled: 0
+  fastroute_hit: 0
+  fastroute_success: 0
+  fastroute_defer: 0
+  fastroute_deferred_out: 0
+  cpu_collision: 0
+  received_rps: 0
+  flow_limit_count: 0
+- processed: 177038867
+  dropped: 0
+  time_squeeze: 413
+  throttled: 0
+  fastroute_hit: 0
+  fastroute_success: 0
+  fastroute_defer: 0
+  fastroute_deferred_out: 0
+  cpu_collision: 0
+  received_rps: 0
+  flow_limit_count: 0
+";
+    assert_eq!(expected, out);
 
-        let _ = parse_softnet_stats(&raw).unwrap();
+    let parsed: Vec<SoftnetStat> = serde_json::from_value(yaml::parse_flat_seq(&out)).unwrap();
+    assert_eq!(stats, parsed);
+}
+
+#[cfg(feature = "yaml")]
+#[test]
+fn test_yaml_report() {
+    let rows = into_rows(vec![SoftnetStat::new(1, 2, 3)]);
+
+    let mut out = Vec::new();
+    yaml(&mut out, &rows, true, Some("2021-10-01T00:00:00Z")).unwrap();
+    let out = String::from_utf8(out).unwrap();
+
+    assert!(out.starts_with(
+        "timestamp: \"2021-10-01T00:00:00Z\"\nmeta:\n  fields:\n  - name: processed\n"
+    ));
+    assert!(out.contains("\n    since: v5.10\n"));
+    assert!(out.ends_with("\nstats:\n- processed: 1\n  dropped: 2\n  time_squeeze: 3\n  throttled: 0\n  fastroute_hit: 0\n  fastroute_success: 0\n  fastroute_defer: 0\n  fastroute_deferred_out: 0\n  cpu_collision: 0\n"));
+}
+
+#[test]
+fn test_has_offline_cpus() {
+    let pwd = env!("CARGO_MANIFEST_DIR");
+    let raw = fs::read(format!(
+        "{}/tests/proc-net-softnet_stat-5_10_47-offline_cpu",
+        pwd
+    ))
+    .unwrap();
+    let (_, stats) = parse_softnet_stats(&raw).unwrap();
+    assert!(has_offline_cpus(&stats));
+
+    for fixture in ["5_10_47", "5_10_47-single_cpu", "4_19", "2_6_32"] {
+        let raw = fs::read(format!("{}/tests/proc-net-softnet_stat-{}", pwd, fixture)).unwrap();
+        let (_, stats) = parse_softnet_stats(&raw).unwrap();
+        assert!(!has_offline_cpus(&stats), "{}", fixture);
+    }
+}
+
+#[test]
+fn test_u32_max_counters() {
+    let max = "ffffffff ".repeat(12) + "00000000\n";
+    let raw = format!(
+        "{}{}",
+        max,
+        max.replace("ffffffff 00000000\n", "ffffffff 00000001\n")
+    );
+    let (_, stats) = parse_softnet_stats(raw.as_bytes()).unwrap();
+    let rows = into_rows(stats.clone());
+    let render = |format: Format, options: &OutputOptions| {
+        let mut out = Vec::new();
+        format.write(&mut out, &rows, options).unwrap();
+        String::from_utf8(out).unwrap()
+    };
+
+    let options = OutputOptions {
+        total: true,
+        ..OutputOptions::default()
+    };
+    let table = render(Format::Table, &options);
+    let lines: Vec<&str> = table.lines().collect();
+    assert_eq!(
+        vec!["0", "4294967295", "4294967295", "50.00", "4294967295"],
+        lines[1].split_whitespace().collect::<Vec<_>>()[..5].to_vec()
+    );
+    // The total of two maxed out CPUs saturates instead of wrapping to a small number.
+    assert_eq!(
+        vec!["Total", "4294967295", "4294967295", "50.00", "4294967295"],
+        lines[4].split_whitespace().collect::<Vec<_>>()[..5].to_vec()
+    );
+    assert!(lines[4].ends_with(" 1.0"));
+
+    let options = OutputOptions {
+        human: true,
+        ..OutputOptions::default()
+    };
+    assert!(render(Format::Table, &options).contains(" 4.3G "));
+
+    let json = render(Format::Json, &OutputOptions::default());
+    let parsed: Vec<SoftnetStat> = serde_json::from_str(&json).unwrap();
+    assert_eq!(stats, parsed);
+
+    let options = OutputOptions {
+        frames_per_squeeze: true,
+        ..OutputOptions::default()
+    };
+    let metrics = render(Format::Prometheus, &options);
+    assert!(metrics.contains("\nsoftnet_frames_processed{cpu=\"cpu1\"} 4294967295\n"));
+    assert!(metrics.contains("\nsoftnet_frames_per_squeeze{cpu=\"cpu0\"} 1\n"));
+    for sample in metrics.lines().filter(|line| !line.starts_with('#')) {
+        let value = sample.rsplit(' ').next().unwrap();
+        assert!(value.parse::<f64>().unwrap().is_finite(), "{}", sample);
     }
 }
+
+#[test]
+fn test_merge_duplicates_saturates() {
+    let raw = "ffffffff 00000001 ".to_string() + &"00000000 ".repeat(10) + "00000003\n";
+    let (_, stats) = parse_softnet_stats(raw.repeat(2).as_bytes()).unwrap();
+
+    let (merged, count) = merge_duplicates(into_rows(stats));
+    assert_eq!(1, count);
+    assert_eq!(u32::MAX, merged[0].stat.processed);
+    assert_eq!(2, merged[0].stat.dropped);
+}
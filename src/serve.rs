@@ -0,0 +1,163 @@
+/*  Parser for /proc/softnet_stats file
+ *  Copyright (C) 2016  Herman J. Radtke III <herman@hermanradtke.com>
+ *
+ *  This program is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! A minimal HTTP server so Prometheus can scrape the metrics directly.
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// The content type of the Prometheus text exposition format.
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+/// How long a client has to send its request, or to read the response, before it is dropped.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The most of a request that is read. Scrapes are a single short GET.
+const MAX_REQUEST: u64 = 16 * 1024;
+
+/// Answer every `GET /metrics` on `listener` with the body returned by `metrics`, forever.
+///
+/// Each connection is handled on its own thread, so a slow client does not hold up other scrapes.
+/// An error from `metrics` is returned to that client as a 500 rather than stopping the server.
+pub fn serve<F>(listener: TcpListener, metrics: F) -> io::Result<()>
+where
+    F: Fn() -> Result<Vec<u8>, String> + Send + Sync + 'static,
+{
+    let metrics = Arc::new(metrics);
+
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                eprintln!("warning: failed to accept a connection: {}", e);
+                continue;
+            }
+        };
+
+        let metrics = Arc::clone(&metrics);
+        let handler = thread::Builder::new().spawn(move || {
+            if let Err(e) = handle(stream, &*metrics) {
+                eprintln!("warning: failed to answer a request: {}", e);
+            }
+        });
+        if let Err(e) = handler {
+            eprintln!("warning: failed to start a thread for a connection: {}", e);
+        }
+    }
+
+    Ok(())
+}
+
+fn handle<F>(stream: TcpStream, metrics: &F) -> io::Result<()>
+where
+    F: Fn() -> Result<Vec<u8>, String>,
+{
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+
+    let mut reader = BufReader::new((&stream).take(MAX_REQUEST));
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+
+    // The headers do not change the response, but have to be read before it is sent.
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 0 && !header.trim_end().is_empty() {
+        header.clear();
+    }
+
+    let mut parts = request.split_whitespace();
+    let method = parts.next();
+    let path = parts
+        .next()
+        .map(|target| target.split('?').next().unwrap_or(target));
+
+    let (status, content_type, body) = match (method, path) {
+        (Some("GET"), Some("/metrics")) => match metrics() {
+            Ok(body) => ("200 OK", CONTENT_TYPE, body),
+            Err(e) => {
+                eprintln!("warning: failed to collect metrics: {}", e);
+                (
+                    "500 Internal Server Error",
+                    "text/plain",
+                    format!("{}\n", e).into_bytes(),
+                )
+            }
+        },
+        (Some("GET"), _) => ("404 Not Found", "text/plain", b"not found\n".to_vec()),
+        _ => (
+            "405 Method Not Allowed",
+            "text/plain",
+            b"method not allowed\n".to_vec(),
+        ),
+    };
+
+    let mut out = &stream;
+    write!(
+        out,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    )?;
+    out.write_all(&body)?;
+    out.flush()
+}
+
+#[cfg(test)]
+fn request(metrics: fn() -> Result<Vec<u8>, String>, request: &str) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    thread::spawn(move || serve(listener, metrics));
+
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream.write_all(request.as_bytes()).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+
+    response
+}
+
+#[test]
+fn test_serve() {
+    let response = request(
+        || Ok(b"softnet_frames_processed{cpu=\"cpu0\"} 1\n".to_vec()),
+        "GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n",
+    );
+
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(response.contains("\r\nContent-Type: text/plain; version=0.0.4\r\n"));
+    assert!(response.ends_with("\r\n\r\nsoftnet_frames_processed{cpu=\"cpu0\"} 1\n"));
+}
+
+#[test]
+fn test_serve_errors() {
+    let response = request(
+        || Err("failed to read /proc/net/softnet_stat".to_string()),
+        "GET /metrics HTTP/1.1\r\n\r\n",
+    );
+    assert!(response.starts_with("HTTP/1.1 500 Internal Server Error\r\n"));
+    assert!(response.ends_with("\r\n\r\nfailed to read /proc/net/softnet_stat\n"));
+
+    let response = request(|| Ok(vec![]), "GET / HTTP/1.1\r\n\r\n");
+    assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+
+    let response = request(|| Ok(vec![]), "POST /metrics HTTP/1.1\r\n\r\n");
+    assert!(response.starts_with("HTTP/1.1 405 Method Not Allowed\r\n"));
+}
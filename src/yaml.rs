@@ -0,0 +1,599 @@
+/*  Parser for /proc/softnet_stats file
+ *  Copyright (C) 2016  Herman J. Radtke III <herman@hermanradtke.com>
+ *
+ *  This program is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Writing serde values as block style YAML.
+//!
+//! serde_yaml cannot be built against here, so this is a small serializer, only compiled with the
+//! `yaml` feature. Values are collected into a tree first so that fields which are `None` can
+//! be left out of their mapping instead of being written as `null`, matching the other formats
+//! where a missing field means the kernel does not report it.
+
+use std::fmt;
+use std::io;
+
+use serde::ser::{self, Serialize};
+
+/// Write `value` as a YAML document.
+pub fn to_writer<W, T>(mut out: W, value: &T) -> io::Result<()>
+where
+    W: io::Write,
+    T: Serialize + ?Sized,
+{
+    let node = value
+        .serialize(NodeSerializer)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+    let mut document = String::new();
+    if block(&node) {
+        render(&node, 0, &mut document);
+    } else {
+        document.push_str(&inline(&node));
+        document.push('\n');
+    }
+
+    out.write_all(document.as_bytes())
+}
+
+/// A serialized value, in the order it was serialized.
+#[derive(Debug, PartialEq)]
+enum Node {
+    Null,
+    Scalar(String),
+    Seq(Vec<Node>),
+    Map(Vec<(String, Node)>),
+}
+
+/// Whether `node` is written as a block, rather than on the line of its key or `-`. Mappings are
+/// only blocks if they have an entry that is not left out for being `None`.
+fn block(node: &Node) -> bool {
+    match node {
+        Node::Map(entries) => entries.iter().any(|(_, value)| *value != Node::Null),
+        Node::Seq(items) => !items.is_empty(),
+        _ => false,
+    }
+}
+
+/// Append the block form of a mapping or sequence for which `block` is true to `out`, indented
+/// by `indent`.
+fn render(node: &Node, indent: usize, out: &mut String) {
+    let pad = " ".repeat(indent);
+
+    match node {
+        Node::Map(entries) => {
+            for (key, value) in entries {
+                if *value == Node::Null {
+                    continue;
+                }
+
+                out.push_str(&pad);
+                out.push_str(key);
+                out.push(':');
+                match value {
+                    Node::Map(_) if block(value) => {
+                        out.push('\n');
+                        render(value, indent + 2, out);
+                    }
+                    // Sequences in a mapping are conventionally not indented past their key.
+                    Node::Seq(_) if block(value) => {
+                        out.push('\n');
+                        render(value, indent, out);
+                    }
+                    _ => {
+                        out.push(' ');
+                        out.push_str(&inline(value));
+                        out.push('\n');
+                    }
+                }
+            }
+        }
+        Node::Seq(items) => {
+            for item in items {
+                out.push_str(&pad);
+                out.push('-');
+                if block(item) {
+                    nested(item, indent, out);
+                } else {
+                    out.push(' ');
+                    out.push_str(&inline(item));
+                    out.push('\n');
+                }
+            }
+        }
+        _ => unreachable!("only collections are rendered as blocks"),
+    }
+}
+
+/// Append a collection that is an item of a sequence, starting on the line of its `-`.
+fn nested(node: &Node, indent: usize, out: &mut String) {
+    let mut block = String::new();
+    render(node, indent + 2, &mut block);
+    out.push(' ');
+    out.push_str(&block[indent + 2..]);
+}
+
+/// The form of a scalar, or an empty collection, that fits on the line of its key.
+fn inline(node: &Node) -> String {
+    match node {
+        Node::Null => "null".to_string(),
+        Node::Scalar(scalar) => scalar.clone(),
+        Node::Seq(_) => "[]".to_string(),
+        Node::Map(_) => "{}".to_string(),
+    }
+}
+
+/// A string as a YAML scalar, quoted unless it could only be read back as the same string.
+fn string(value: &str) -> String {
+    const RESERVED: [&str; 11] = [
+        "true", "false", "yes", "no", "on", "off", "y", "n", "null", "none", "nan",
+    ];
+
+    let plain = value.starts_with(|c: char| c.is_ascii_alphabetic())
+        && !value.ends_with(' ')
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || " ._/-".contains(c))
+        && !RESERVED.contains(&value.to_ascii_lowercase().as_str());
+
+    if plain {
+        value.to_string()
+    } else {
+        // A json string is also a valid double-quoted YAML scalar.
+        serde_json::to_string(value).expect("strings always serialize")
+    }
+}
+
+#[derive(Debug)]
+pub struct Error(String);
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl ser::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error(msg.to_string())
+    }
+}
+
+/// Serializes a value into a `Node`.
+struct NodeSerializer;
+
+/// Collects the items of a sequence, tuple or tuple struct.
+struct SeqSerializer(Vec<Node>);
+
+/// Collects the entries of a map or struct.
+struct MapSerializer {
+    entries: Vec<(String, Node)>,
+    key: Option<String>,
+}
+
+/// Collects a variant with fields, which is written as a mapping from the variant name to them.
+struct VariantSerializer<S> {
+    variant: &'static str,
+    fields: S,
+}
+
+fn scalar<T: ToString>(value: T) -> Result<Node, Error> {
+    Ok(Node::Scalar(value.to_string()))
+}
+
+fn variant(name: &'static str, value: Node) -> Node {
+    Node::Map(vec![(name.to_string(), value)])
+}
+
+impl ser::Serializer for NodeSerializer {
+    type Ok = Node;
+    type Error = Error;
+    type SerializeSeq = SeqSerializer;
+    type SerializeTuple = SeqSerializer;
+    type SerializeTupleStruct = SeqSerializer;
+    type SerializeTupleVariant = VariantSerializer<SeqSerializer>;
+    type SerializeMap = MapSerializer;
+    type SerializeStruct = MapSerializer;
+    type SerializeStructVariant = VariantSerializer<MapSerializer>;
+
+    fn serialize_bool(self, v: bool) -> Result<Node, Error> {
+        scalar(v)
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Node, Error> {
+        scalar(v)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Node, Error> {
+        scalar(v)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Node, Error> {
+        scalar(v)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Node, Error> {
+        scalar(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Node, Error> {
+        scalar(v)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Node, Error> {
+        scalar(v)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Node, Error> {
+        scalar(v)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Node, Error> {
+        scalar(v)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Node, Error> {
+        self.serialize_f64(f64::from(v))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Node, Error> {
+        if v.is_nan() {
+            scalar(".nan")
+        } else if v.is_infinite() {
+            scalar(if v > 0.0 { ".inf" } else { "-.inf" })
+        } else {
+            // Keep a decimal point so the value reads back as a float.
+            scalar(format!("{:?}", v))
+        }
+    }
+
+    fn serialize_char(self, v: char) -> Result<Node, Error> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Node, Error> {
+        Ok(Node::Scalar(string(v)))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Node, Error> {
+        Ok(Node::Seq(
+            v.iter().map(|b| Node::Scalar(b.to_string())).collect(),
+        ))
+    }
+
+    fn serialize_none(self) -> Result<Node, Error> {
+        Ok(Node::Null)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Node, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Node, Error> {
+        Ok(Node::Null)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Node, Error> {
+        Ok(Node::Null)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<Node, Error> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Node, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        name: &'static str,
+        value: &T,
+    ) -> Result<Node, Error> {
+        Ok(variant(name, value.serialize(self)?))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SeqSerializer, Error> {
+        Ok(SeqSerializer(Vec::with_capacity(len.unwrap_or_default())))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SeqSerializer, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SeqSerializer, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<VariantSerializer<SeqSerializer>, Error> {
+        Ok(VariantSerializer {
+            variant,
+            fields: self.serialize_seq(Some(len))?,
+        })
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<MapSerializer, Error> {
+        Ok(MapSerializer {
+            entries: Vec::with_capacity(len.unwrap_or_default()),
+            key: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<MapSerializer, Error> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<VariantSerializer<MapSerializer>, Error> {
+        Ok(VariantSerializer {
+            variant,
+            fields: self.serialize_map(Some(len))?,
+        })
+    }
+}
+
+impl ser::SerializeSeq for SeqSerializer {
+    type Ok = Node;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.0.push(value.serialize(NodeSerializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Node, Error> {
+        Ok(Node::Seq(self.0))
+    }
+}
+
+impl ser::SerializeTuple for SeqSerializer {
+    type Ok = Node;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Node, Error> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleStruct for SeqSerializer {
+    type Ok = Node;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Node, Error> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleVariant for VariantSerializer<SeqSerializer> {
+    type Ok = Node;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        ser::SerializeSeq::serialize_element(&mut self.fields, value)
+    }
+
+    fn end(self) -> Result<Node, Error> {
+        Ok(variant(self.variant, ser::SerializeSeq::end(self.fields)?))
+    }
+}
+
+impl ser::SerializeMap for MapSerializer {
+    type Ok = Node;
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+        match key.serialize(NodeSerializer)? {
+            Node::Scalar(key) => {
+                self.key = Some(key);
+                Ok(())
+            }
+            _ => Err(Error("map keys must be scalars".to_string())),
+        }
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| Error("map value without a key".to_string()))?;
+        self.entries.push((key, value.serialize(NodeSerializer)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Node, Error> {
+        Ok(Node::Map(self.entries))
+    }
+}
+
+impl ser::SerializeStruct for MapSerializer {
+    type Ok = Node;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.entries
+            .push((string(key), value.serialize(NodeSerializer)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Node, Error> {
+        ser::SerializeMap::end(self)
+    }
+}
+
+impl ser::SerializeStructVariant for VariantSerializer<MapSerializer> {
+    type Ok = Node;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        ser::SerializeStruct::serialize_field(&mut self.fields, key, value)
+    }
+
+    fn end(self) -> Result<Node, Error> {
+        Ok(variant(self.variant, ser::SerializeMap::end(self.fields)?))
+    }
+}
+
+/// Read back the subset of YAML that `to_writer` writes for a sequence of flat mappings, as json.
+#[cfg(test)]
+pub fn parse_flat_seq(document: &str) -> serde_json::Value {
+    let mut items = vec![];
+    for line in document.lines() {
+        let entry = match line.strip_prefix("- ") {
+            Some(entry) => {
+                items.push(serde_json::Map::new());
+                entry
+            }
+            None => line.strip_prefix("  ").expect("an indented entry"),
+        };
+
+        let (key, value) = entry.split_once(": ").expect("a key and value");
+        let value = serde_json::from_str(value)
+            .unwrap_or_else(|_| serde_json::Value::String(value.to_string()));
+        items
+            .last_mut()
+            .expect("an entry inside an item")
+            .insert(key.to_string(), value);
+    }
+
+    serde_json::Value::Array(items.into_iter().map(serde_json::Value::Object).collect())
+}
+
+#[cfg(test)]
+fn to_string<T: Serialize>(value: &T) -> String {
+    let mut out = Vec::new();
+    to_writer(&mut out, value).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn test_to_writer() {
+    #[derive(serde_derive::Serialize)]
+    struct Field {
+        name: &'static str,
+        since: Option<&'static str>,
+        aliases: Vec<&'static str>,
+    }
+
+    #[derive(serde_derive::Serialize)]
+    struct Report {
+        timestamp: &'static str,
+        fields: Vec<Field>,
+        empty: Vec<u32>,
+        ratio: f64,
+    }
+
+    let report = Report {
+        timestamp: "2021-10-01T00:00:00Z",
+        fields: vec![
+            Field {
+                name: "processed",
+                since: None,
+                aliases: vec![],
+            },
+            Field {
+                name: "cpu_id",
+                since: Some("v5.10"),
+                aliases: vec!["id", "true"],
+            },
+        ],
+        empty: vec![],
+        ratio: 2.0,
+    };
+
+    assert_eq!(
+        "timestamp: \"2021-10-01T00:00:00Z\"
+fields:
+- name: processed
+  aliases: []
+- name: cpu_id
+  since: v5.10
+  aliases:
+  - id
+  - \"true\"
+empty: []
+ratio: 2.0
+",
+        to_string(&report)
+    );
+    assert_eq!("[]\n", to_string(&Vec::<u32>::new()));
+    assert_eq!("\"a: b\"\n", to_string(&"a: b"));
+}
+
+#[test]
+fn test_to_writer_all_none() {
+    #[derive(serde_derive::Serialize)]
+    struct Optional {
+        since: Option<&'static str>,
+        removed: Option<&'static str>,
+    }
+
+    #[derive(serde_derive::Serialize)]
+    struct Wrapper {
+        field: Optional,
+    }
+
+    let none = || Optional {
+        since: None,
+        removed: None,
+    };
+
+    assert_eq!("{}\n", to_string(&none()));
+    assert_eq!("- {}\n- {}\n", to_string(&vec![none(), none()]));
+    assert_eq!("field: {}\n", to_string(&Wrapper { field: none() }));
+}
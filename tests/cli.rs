@@ -0,0 +1,430 @@
+/*  Parser for /proc/softnet_stats file
+ *  Copyright (C) 2016  Herman J. Radtke III <herman@hermanradtke.com>
+ *
+ *  This program is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Run the `softnet-stat` binary against the fixtures in this directory.
+
+#![cfg(feature = "cli")]
+
+use std::env;
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::process::{self, Command, Output, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn fixture(name: &str) -> String {
+    format!("{}/tests/{}", env!("CARGO_MANIFEST_DIR"), name)
+}
+
+fn softnet_stat(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_softnet-stat"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_file() {
+    let output = softnet_stat(&["--file", &fixture("proc-net-softnet_stat-3_11")]);
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<Vec<&str>> = stdout
+        .lines()
+        .map(|line| line.split_whitespace().collect())
+        .collect();
+
+    assert_eq!(10, lines.len());
+    assert_eq!(
+        vec!["Kernel", "v3.11", "to", "v5.9"],
+        lines[0][..4].to_vec()
+    );
+    assert_eq!(vec!["Cpu", "Processed", "Dropped"], lines[1][..3].to_vec());
+    assert_eq!(vec!["0", "142307", "0"], lines[2][..3].to_vec());
+    assert_eq!(vec!["7", "3251", "0"], lines[9][..3].to_vec());
+}
+
+#[test]
+fn test_no_header() {
+    let file = fixture("proc-net-softnet_stat-2_6_32");
+
+    let output = softnet_stat(&["--file", &file]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(
+        Some("Kernel before v2.6.36 (inferred from the fields present)"),
+        stdout.lines().next()
+    );
+
+    let output = softnet_stat(&["--file", &file, "--no-header"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("Cpu "));
+
+    // Only the table has the header.
+    let output = softnet_stat(&["--file", &file, "--csv"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("cpu,"));
+}
+
+#[test]
+fn test_offline_cpus() {
+    let file = fixture("proc-net-softnet_stat-5_10_47-offline_cpu");
+
+    let output = softnet_stat(&["--file", &file]);
+    assert!(output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.starts_with("note: some CPUs in "));
+    assert!(stderr.contains(" are offline"));
+
+    // The other formats label rows with the cpu_id, so they have nothing to note.
+    let output = softnet_stat(&["--file", &file, "--csv"]);
+    assert!(output.stderr.is_empty());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("\n2,"));
+}
+
+#[test]
+fn test_file_and_stdin() {
+    let output = softnet_stat(&["--stdin", "--file", &fixture("proc-net-softnet_stat-3_11")]);
+    assert_eq!(Some(1), output.status.code());
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(
+        "softnet-stat: --file and --stdin cannot be used together\n",
+        stderr
+    );
+}
+
+#[test]
+fn test_missing_file() {
+    let output = softnet_stat(&["--file", &fixture("does-not-exist")]);
+    assert_eq!(Some(1), output.status.code());
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn test_fail_on_drops() {
+    let drops = fixture("proc-net-softnet_stat-5_10_47-drops");
+
+    let output = softnet_stat(&["--file", &drops, "--fail-on-drops"]);
+    assert_eq!(Some(2), output.status.code());
+    // The stats are still printed before failing.
+    assert_eq!(6, String::from_utf8(output.stdout).unwrap().lines().count());
+    assert_eq!(
+        "cpu2: dropped is 42, above the threshold of 0\n",
+        String::from_utf8(output.stderr).unwrap()
+    );
+
+    let output = softnet_stat(&["--file", &drops, "--fail-on-drops=42"]);
+    assert!(output.status.success());
+
+    let output = softnet_stat(&[
+        "--file",
+        &fixture("proc-net-softnet_stat-3_11"),
+        "--fail-on-drops",
+    ]);
+    assert!(output.status.success());
+}
+
+#[test]
+fn test_fail_on_squeeze() {
+    let drops = fixture("proc-net-softnet_stat-5_10_47-drops");
+
+    let output = softnet_stat(&["--file", &drops, "--fail-on-squeeze=3000"]);
+    assert_eq!(Some(2), output.status.code());
+    assert_eq!(
+        "cpu2: time_squeeze is 4804, above the threshold of 3000\n",
+        String::from_utf8(output.stderr).unwrap()
+    );
+
+    let output = softnet_stat(&["--file", &drops, "--fail-on-squeeze=lots"]);
+    assert_eq!(Some(1), output.status.code());
+}
+
+#[test]
+fn test_serve() {
+    let mut server = Command::new(env!("CARGO_BIN_EXE_softnet-stat"))
+        .args(["--serve", "127.0.0.1:0", "--file"])
+        .arg(fixture("proc-net-softnet_stat-5_10_47-drops"))
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    let mut stderr = BufReader::new(server.stderr.take().unwrap());
+    let mut line = String::new();
+    stderr.read_line(&mut line).unwrap();
+    let addr = line
+        .trim()
+        .strip_prefix("listening on ")
+        .unwrap()
+        .to_string();
+
+    let mut stream = TcpStream::connect(&addr).unwrap();
+    stream
+        .write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    server.kill().unwrap();
+    server.wait().unwrap();
+
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(head.contains("\r\nContent-Type: text/plain; version=0.0.4"));
+    assert!(body.starts_with("# HELP softnet_frames_processed "));
+    assert!(body.contains("\nsoftnet_frames_dropped{cpu=\"cpu2\"} 42\n"));
+}
+
+#[test]
+fn test_serve_invalid_cpu() {
+    // A bad --cpu is reported before listening, rather than on every scrape.
+    let output = softnet_stat(&[
+        "--serve",
+        "127.0.0.1:0",
+        "--cpu",
+        "bogus",
+        "--file",
+        &fixture("proc-net-softnet_stat-5_10_47-drops"),
+    ]);
+    assert_eq!(Some(1), output.status.code());
+    assert_eq!(
+        "softnet-stat: invalid --cpu: `bogus` is not a cpu\n",
+        String::from_utf8(output.stderr).unwrap()
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_watch_stdin_interrupt() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_softnet-stat"))
+        .args(["--stdin", "--watch", "0.1", "--csv"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    // Send one snapshot and keep stdin open, so the watch is blocked reading the next one.
+    let mut stdin = child.stdin.take().unwrap();
+    stdin
+        .write_all(&fs::read(fixture("proc-net-softnet_stat-3_11")).unwrap())
+        .unwrap();
+    stdin.write_all(b"\n").unwrap();
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let mut line = String::new();
+    stdout.read_line(&mut line).unwrap();
+    assert!(line.starts_with("cpu,processed,"));
+
+    // Wait out the interval, so SIGINT arrives while the watch is blocked on stdin rather than
+    // while it sleeps.
+    thread::sleep(Duration::from_millis(500));
+    let status = Command::new("kill")
+        .args(["-INT", &child.id().to_string()])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let deadline = Instant::now() + Duration::from_secs(5);
+    let status = loop {
+        if let Some(status) = child.try_wait().unwrap() {
+            break status;
+        }
+        if Instant::now() > deadline {
+            child.kill().unwrap();
+            panic!("--stdin --watch did not stop on SIGINT");
+        }
+        thread::sleep(Duration::from_millis(10));
+    };
+    assert!(status.success());
+    drop(stdin);
+}
+
+#[test]
+fn test_metric_prefix() {
+    let file = fixture("proc-net-softnet_stat-3_11");
+
+    let output = softnet_stat(&["--file", &file, "-p", "--metric-prefix", "node_softnet"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("# HELP node_softnet_frames_processed "));
+    assert!(stdout.contains("\n# TYPE node_softnet_frames_processed counter\n"));
+
+    let output = softnet_stat(&["--file", &file, "-p", "--metric-prefix", "0softnet"]);
+    assert_eq!(Some(1), output.status.code());
+    assert!(output.stdout.is_empty());
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .starts_with("softnet-stat: invalid --metric-prefix: "));
+}
+
+#[test]
+fn test_color() {
+    let drops = fixture("proc-net-softnet_stat-5_10_47-drops");
+
+    let output = softnet_stat(&["--file", &drops, "--color", "never"]);
+    assert!(output.status.success());
+    assert!(!output.stdout.contains(&0x1b));
+
+    let output = softnet_stat(&["--file", &drops, "--color", "always"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("\n\x1b[31m2 "));
+
+    // The other formats are never colored.
+    let output = softnet_stat(&["--file", &drops, "--color", "always", "--json"]);
+    assert!(!output.stdout.contains(&0x1b));
+    let output = softnet_stat(&["--file", &drops, "--color", "always", "-p"]);
+    assert!(!output.stdout.contains(&0x1b));
+
+    let output = softnet_stat(&["--file", &drops, "--color", "sometimes"]);
+    assert_eq!(Some(1), output.status.code());
+}
+
+#[test]
+fn test_diff() {
+    let old = fixture("proc-net-softnet_stat-5_10_47");
+    let new = fixture("proc-net-softnet_stat-5_10_47-drops");
+
+    let output = softnet_stat(&["--diff", &old, &new]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<Vec<&str>> = stdout
+        .lines()
+        .map(|line| line.split_whitespace().collect())
+        .collect();
+
+    assert_eq!(5, lines.len());
+    assert_eq!(vec!["0", "808708", "0", "1692"], lines[1][..4].to_vec());
+    assert_eq!(vec!["2", "only", "in", "NEW"], lines[3]);
+
+    let output = softnet_stat(&["--diff", &new, &old]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.lines().nth(1).unwrap().ends_with("counter reset"));
+
+    let output = softnet_stat(&["--diff", &old]);
+    assert_eq!(Some(1), output.status.code());
+    assert_eq!(
+        "softnet-stat: --diff needs the OLD and NEW files to compare\n",
+        String::from_utf8(output.stderr).unwrap()
+    );
+
+    // Options that select or check rows are not applied to either snapshot, so they are refused.
+    let output = softnet_stat(&["--diff", &old, &new, "--cpu", "0"]);
+    assert_eq!(Some(1), output.status.code());
+    assert_eq!(
+        "softnet-stat: --diff cannot be combined with --cpu\n",
+        String::from_utf8(output.stderr).unwrap()
+    );
+}
+
+#[test]
+fn test_unexpected_argument() {
+    let file = fixture("proc-net-softnet_stat-3_11");
+
+    let output = softnet_stat(&["--file", &file, "stray-arg"]);
+    assert_eq!(Some(1), output.status.code());
+    assert!(output.stdout.is_empty());
+    assert_eq!(
+        "softnet-stat: unexpected argument `stray-arg`\n",
+        String::from_utf8(output.stderr).unwrap()
+    );
+}
+
+#[cfg(feature = "gzip")]
+#[test]
+fn test_gzip() {
+    let gzip = fixture("proc-net-softnet_stat-5_10_47-32_cpu-gzip");
+
+    let output = softnet_stat(&["--file", &gzip, "--csv"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(33, stdout.lines().count());
+    assert!(stdout.lines().nth(32).unwrap().starts_with("31,"));
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_softnet-stat"))
+        .args(["--stdin", "--csv"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(&std::fs::read(&gzip).unwrap())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(stdout, String::from_utf8(output.stdout).unwrap());
+}
+
+#[test]
+fn test_parse_error() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_softnet-stat"))
+        .arg("--stdin")
+        .stdin(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(
+            b"00000001 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000
+00000001 00000000 0000zz00 00000000 00000000 00000000 00000000 00000000 00000000
+",
+        )
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+
+    assert_eq!(Some(1), output.status.code());
+    assert_eq!(
+        "softnet-stat: stdin: parse error at line 2, column 23: expected hex digit, found `z`\n",
+        String::from_utf8(output.stderr).unwrap()
+    );
+}
+
+#[test]
+fn test_output() {
+    let dir = env::temp_dir().join(format!("softnet-stat-output-{}", process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("softnet.csv");
+    let path = path.to_str().unwrap();
+
+    let output = softnet_stat(&[
+        "--file",
+        &fixture("proc-net-softnet_stat-3_11"),
+        "--csv",
+        "--output",
+        path,
+    ]);
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+
+    let written = fs::read_to_string(path).unwrap();
+    let names: Vec<String> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    fs::remove_dir_all(&dir).unwrap();
+
+    let stdout = softnet_stat(&["--file", &fixture("proc-net-softnet_stat-3_11"), "--csv"]).stdout;
+    assert_eq!(String::from_utf8(stdout).unwrap(), written);
+    assert!(written.starts_with("cpu,processed,"));
+    // The temporary file was renamed into place rather than left behind.
+    assert_eq!(vec!["softnet.csv".to_string()], names);
+}